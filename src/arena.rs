@@ -0,0 +1,414 @@
+use std::f32::consts::TAU;
+use std::fmt;
+use std::fs;
+
+use bevy::prelude::*;
+use rand::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::components::WallShape;
+use crate::systems::PLAYER_SIZE;
+
+pub const ARENA_PATH: &str = "assets/arenas/default.ron";
+//built-in copy of the default arena, used when the arena file can't be loaded
+pub const DEFAULT_ARENA: &str = include_str!("../assets/arenas/default.ron");
+
+//path of the arena file that was loaded, the editor saves back to it
+#[derive(Resource)]
+pub struct ArenaFile {
+    pub path: String,
+}
+
+/*
+ arena layout as it is written in a RON file, every position is in world space
+ with (0, 0) at the bottom left corner of the arena
+*/
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Arena {
+    pub name: String,
+    pub size: Vec2, //width and height of the arena
+    pub player_start: Vec2,
+    #[serde(default)]
+    pub boundary: Boundary,
+    #[serde(default)]
+    pub background: Background,
+    #[serde(default)]
+    pub walls: Vec<WallLayout>,
+    #[serde(default)]
+    pub enemy_spawn_regions: Vec<SpawnRegion>, //empty means the whole arena
+    #[serde(default)]
+    pub star_spawn_regions: Vec<SpawnRegion>, //empty means the whole arena
+}
+
+//what happens at the edges of the arena
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Boundary {
+    #[default]
+    Solid, //things stop or bounce at the edges
+    Wrap,   //things leaving one edge come back in at the opposite edge
+    Circle, //round arena as wide as the shorter side, things bounce off the rim
+}
+
+impl Boundary {
+    //the next boundary mode, for cycling through them in the editor
+    pub fn next(&self) -> Boundary {
+        match self {
+            Boundary::Solid => Boundary::Wrap,
+            Boundary::Wrap => Boundary::Circle,
+            Boundary::Circle => Boundary::Solid,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Background {
+    pub color: (f32, f32, f32), //rgb, every channel between 0 and 1
+    #[serde(default)]
+    pub image: Option<String>, //asset path, stretched over the whole arena
+}
+
+//give default value
+impl Default for Background {
+    fn default() -> Background {
+        Background {
+            color: (0.4, 0.4, 0.4),
+            image: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub enum WallLayout {
+    Rect { center: Vec2, size: Vec2 },
+    Circle { center: Vec2, radius: f32 },
+}
+
+impl WallLayout {
+    pub fn center(&self) -> Vec2 {
+        match *self {
+            WallLayout::Rect { center, .. } => center,
+            WallLayout::Circle { center, .. } => center,
+        }
+    }
+
+    pub fn shape(&self) -> WallShape {
+        match *self {
+            WallLayout::Rect { size, .. } => WallShape::Rect {
+                half_size: size / 2.0,
+            },
+            WallLayout::Circle { radius, .. } => WallShape::Circle { radius },
+        }
+    }
+}
+
+//axis aligned rectangle that things can spawn in
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnRegion {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl SpawnRegion {
+    pub fn area(&self) -> f32 {
+        let size = self.max - self.min;
+        size.x * size.y
+    }
+
+    pub fn random_point(&self) -> Vec2 {
+        self.min + (self.max - self.min) * Vec2::new(random::<f32>(), random::<f32>())
+    }
+}
+
+//error from loading an arena file, each one keeps the path of the file it came from
+#[derive(Debug)]
+pub enum ArenaError {
+    Io {
+        path: String,
+        error: std::io::Error,
+    },
+    Parse {
+        path: String,
+        error: ron::error::SpannedError,
+    },
+    Invalid {
+        path: String,
+        field: String,
+        message: String,
+    },
+    Serialize {
+        path: String,
+        error: ron::Error,
+    },
+}
+
+impl fmt::Display for ArenaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArenaError::Io { path, error } => write!(f, "{}: {}", path, error),
+            ArenaError::Parse { path, error } => write!(
+                f,
+                "{}:{}:{}: {}",
+                path, error.position.line, error.position.col, error.code
+            ),
+            ArenaError::Invalid {
+                path,
+                field,
+                message,
+            } => write!(f, "{}: {}: {}", path, field, message),
+            ArenaError::Serialize { path, error } => write!(f, "{}: {}", path, error),
+        }
+    }
+}
+
+impl std::error::Error for ArenaError {}
+
+impl Arena {
+    //read and validate an arena file
+    pub fn load(path: &str) -> Result<Arena, ArenaError> {
+        let source = fs::read_to_string(path).map_err(|error| ArenaError::Io {
+            path: path.to_string(),
+            error,
+        })?;
+        Arena::parse(path, &source)
+    }
+
+    //parse and validate arena source text, path is only used for error messages
+    pub fn parse(path: &str, source: &str) -> Result<Arena, ArenaError> {
+        let arena: Arena = ron::from_str(source).map_err(|error| ArenaError::Parse {
+            path: path.to_string(),
+            error,
+        })?;
+        arena
+            .validate()
+            .map_err(|(field, message)| ArenaError::Invalid {
+                path: path.to_string(),
+                field,
+                message,
+            })?;
+        Ok(arena)
+    }
+
+    //load the arena at path, reporting any error and falling back to the built-in default arena
+    pub fn load_or_default(path: &str) -> Arena {
+        match Arena::load(path) {
+            Ok(arena) => arena,
+            Err(error) => {
+                eprintln!("Failed to load arena: {}", error);
+                Arena::parse("<built-in>", DEFAULT_ARENA).expect("built-in arena is valid")
+            }
+        }
+    }
+
+    //validate and write the arena to path as pretty printed RON
+    pub fn save(&self, path: &str) -> Result<(), ArenaError> {
+        self.validate()
+            .map_err(|(field, message)| ArenaError::Invalid {
+                path: path.to_string(),
+                field,
+                message,
+            })?;
+        let source =
+            ron::ser::to_string_pretty(self, PrettyConfig::default()).map_err(|error| {
+                ArenaError::Serialize {
+                    path: path.to_string(),
+                    error,
+                }
+            })?;
+        fs::write(path, source).map_err(|error| ArenaError::Io {
+            path: path.to_string(),
+            error,
+        })
+    }
+
+    pub fn width(&self) -> f32 {
+        self.size.x
+    }
+
+    pub fn height(&self) -> f32 {
+        self.size.y
+    }
+
+    pub fn center(&self) -> Vec2 {
+        self.size / 2.0
+    }
+
+    //move a position that left a wrapping arena back in at the opposite edge
+    pub fn wrap(&self, translation: Vec3) -> Vec3 {
+        Vec3::new(
+            translation.x.rem_euclid(self.size.x),
+            translation.y.rem_euclid(self.size.y),
+            translation.z,
+        )
+    }
+
+    //shortest offset from one point to another, going across the seam when the arena wraps
+    pub fn offset(&self, from: Vec2, to: Vec2) -> Vec2 {
+        let offset = to - from;
+        match self.boundary {
+            Boundary::Wrap => offset - (offset / self.size).round() * self.size,
+            _ => offset,
+        }
+    }
+
+    pub fn distance(&self, a: Vec3, b: Vec3) -> f32 {
+        self.offset(a.truncate(), b.truncate()).length()
+    }
+
+    //keep a view of half size half_view centred on center inside the arena, axes where the view is bigger than the arena stay centred
+    pub fn clamp_view(&self, center: Vec2, half_view: Vec2) -> Vec2 {
+        let min = half_view;
+        let max = self.size - half_view;
+        Vec2::new(
+            if min.x > max.x {
+                self.size.x / 2.0
+            } else {
+                center.x.clamp(min.x, max.x)
+            },
+            if min.y > max.y {
+                self.size.y / 2.0
+            } else {
+                center.y.clamp(min.y, max.y)
+            },
+        )
+    }
+
+    //radius of a circular arena
+    pub fn radius(&self) -> f32 {
+        self.size.min_element() / 2.0
+    }
+
+    //outward normal of the rim when a circle at position reaches past the edge of a circular arena
+    pub fn rim_normal(&self, position: Vec2, radius: f32) -> Option<Vec2> {
+        let offset = position - self.center();
+        let distance = offset.length();
+        if self.boundary != Boundary::Circle || distance <= self.radius() - radius {
+            return None;
+        }
+        Some(if distance > 0.0 {
+            offset / distance
+        } else {
+            Vec2::Y
+        })
+    }
+
+    //pull a circle at position back inside the rim of a circular arena
+    pub fn clamp_to_rim(&self, position: Vec2, radius: f32) -> Vec2 {
+        self.center() + (position - self.center()).clamp_length_max(self.radius() - radius)
+    }
+
+    //whether a circle at point fits inside the arena's boundary
+    pub fn is_inside(&self, point: Vec2, radius: f32) -> bool {
+        match self.boundary {
+            Boundary::Circle => point.distance(self.center()) <= self.radius() - radius,
+            _ => point.cmpge(Vec2::splat(radius)).all() && point.cmple(self.size - radius).all(),
+        }
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(Vec2::ZERO).all() && point.cmple(self.size).all()
+    }
+
+    //check the values serde can't, returning the offending field and what is wrong with it
+    fn validate(&self) -> Result<(), (String, String)> {
+        //every comparison with NaN is false, so non-finite numbers are checked for first
+        if !self.size.is_finite() || self.size.x <= 0.0 || self.size.y <= 0.0 {
+            return Err((
+                "size".to_string(),
+                "must be finite and greater than zero".to_string(),
+            ));
+        }
+        let (r, g, b) = self.background.color;
+        if [r, g, b]
+            .iter()
+            .any(|channel| !(0.0..=1.0).contains(channel))
+        {
+            return Err((
+                "background.color".to_string(),
+                "every channel must be between 0 and 1".to_string(),
+            ));
+        }
+        if !self.player_start.is_finite() {
+            return Err(("player_start".to_string(), "must be finite".to_string()));
+        }
+        if !self.is_inside(self.player_start, 0.0) {
+            return Err((
+                "player_start".to_string(),
+                "must be inside the arena".to_string(),
+            ));
+        }
+
+        for (index, wall) in self.walls.iter().enumerate() {
+            let valid = match *wall {
+                WallLayout::Rect { center, size } => {
+                    center.is_finite() && size.is_finite() && size.x > 0.0 && size.y > 0.0
+                }
+                WallLayout::Circle { center, radius } => {
+                    center.is_finite() && radius.is_finite() && radius > 0.0
+                }
+            };
+            if !valid {
+                return Err((
+                    format!("walls[{}]", index),
+                    "center must be finite, size and radius finite and greater than zero"
+                        .to_string(),
+                ));
+            }
+            if wall
+                .shape()
+                .collide_circle(wall.center(), self.player_start, PLAYER_SIZE / 2.0)
+                .is_some()
+            {
+                return Err((
+                    "player_start".to_string(),
+                    format!("overlaps walls[{}]", index),
+                ));
+            }
+        }
+
+        let regions = [
+            ("enemy_spawn_regions", &self.enemy_spawn_regions),
+            ("star_spawn_regions", &self.star_spawn_regions),
+        ];
+        for (name, regions) in regions {
+            for (index, region) in regions.iter().enumerate() {
+                if !region.min.is_finite() || !region.max.is_finite() {
+                    return Err((
+                        format!("{}[{}]", name, index),
+                        "min and max must be finite".to_string(),
+                    ));
+                }
+                if region.min.cmpge(region.max).any() {
+                    return Err((
+                        format!("{}[{}]", name, index),
+                        "min must be smaller than max".to_string(),
+                    ));
+                }
+                if !self.contains(region.min) || !self.contains(region.max) {
+                    return Err((
+                        format!("{}[{}]", name, index),
+                        "must be inside the arena".to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    //random point in one of the regions, bigger regions are picked more often
+    pub fn random_point(&self, regions: &[SpawnRegion]) -> Vec2 {
+        match regions.choose_weighted(&mut thread_rng(), |region| region.area()) {
+            Ok(region) => region.random_point(),
+            Err(_) if self.boundary == Boundary::Circle => {
+                //uniform over the disk, the square root keeps points from bunching up in the middle
+                let distance = self.radius() * random::<f32>().sqrt();
+                self.center() + Vec2::from_angle(random::<f32>() * TAU) * distance
+            }
+            Err(_) => self.size * Vec2::new(random::<f32>(), random::<f32>()),
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::{HashMap, VecDeque};
+
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::events::*;
+use crate::resources::Settings;
+
+//what a sound is for, every kind has its own volume, voice limit and pitch variation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SoundKind {
+    Bounce,
+    StarCollected,
+    Graze,
+    PowerUp,
+    PlayerHit,
+    BossDefeated,
+}
+
+impl SoundKind {
+    pub const ALL: [SoundKind; 6] = [
+        SoundKind::Bounce,
+        SoundKind::StarCollected,
+        SoundKind::Graze,
+        SoundKind::PowerUp,
+        SoundKind::PlayerHit,
+        SoundKind::BossDefeated,
+    ];
+
+    //audio files of the kind, one is picked at random every time it plays
+    pub fn paths(&self) -> &'static [&'static str] {
+        match self {
+            SoundKind::Bounce => &["audio/pluck_001.ogg", "audio/pluck_002.ogg"],
+            SoundKind::StarCollected => &["audio/laserLarge_000.ogg"],
+            SoundKind::Graze => &["audio/pluck_002.ogg"],
+            SoundKind::PowerUp => &["audio/laserLarge_000.ogg"],
+            SoundKind::PlayerHit => &["audio/explosionCrunch_000.ogg"],
+            SoundKind::BossDefeated => &["audio/explosionCrunch_000.ogg"],
+        }
+    }
+
+    //volume relative to the sfx volume, bounces happen all the time so they are quieter
+    pub fn volume(&self) -> f32 {
+        match self {
+            SoundKind::Bounce => 0.5,
+            SoundKind::Graze => 0.7,
+            _ => 1.0,
+        }
+    }
+
+    //most voices of the kind playing at once, more are dropped
+    pub fn max_voices(&self) -> usize {
+        match self {
+            SoundKind::Bounce => 4,
+            SoundKind::StarCollected => 3,
+            SoundKind::Graze | SoundKind::PowerUp => 2,
+            SoundKind::PlayerHit | SoundKind::BossDefeated => 1,
+        }
+    }
+
+    //seconds a voice counts as playing, roughly the length of the sound
+    pub fn voice_time(&self) -> f64 {
+        match self {
+            SoundKind::Bounce | SoundKind::Graze => 0.25,
+            SoundKind::StarCollected | SoundKind::PowerUp => 0.4,
+            SoundKind::PlayerHit | SoundKind::BossDefeated => 1.0,
+        }
+    }
+
+    //playback speed and how far it is randomly moved either way, so repeats don't sound identical
+    pub fn pitch(&self) -> (f32, f32) {
+        match self {
+            SoundKind::Bounce => (1.0, 0.15),
+            SoundKind::StarCollected => (1.0, 0.05),
+            SoundKind::Graze => (1.5, 0.1),
+            SoundKind::PowerUp => (0.8, 0.05),
+            SoundKind::PlayerHit => (1.0, 0.0),
+            SoundKind::BossDefeated => (0.7, 0.0),
+        }
+    }
+}
+
+//preloaded sounds of every kind, and when their voices started
+#[derive(Resource, Default)]
+pub struct SoundLibrary {
+    pub handles: HashMap<SoundKind, Vec<Handle<AudioSource>>>,
+    pub voices: HashMap<SoundKind, VecDeque<f64>>,
+}
+
+impl SoundLibrary {
+    //play a sound of the kind unless too many of them are already playing
+    pub fn play(&mut self, kind: SoundKind, audio: &Audio, settings: &Settings, now: f64) {
+        let voices = self.voices.entry(kind).or_default();
+        while voices
+            .front()
+            .is_some_and(|start| now - start > kind.voice_time())
+        {
+            voices.pop_front();
+        }
+        if voices.len() >= kind.max_voices() {
+            return;
+        }
+
+        let Some(handle) = self
+            .handles
+            .get(&kind)
+            .and_then(|handles| handles.choose(&mut thread_rng()))
+        else {
+            return;
+        };
+        let (speed, variation) = kind.pitch();
+        let speed = speed * (1.0 + thread_rng().gen_range(-variation..=variation));
+        audio.play_with_settings(
+            handle.clone(),
+            PlaybackSettings::ONCE
+                .with_volume(kind.volume() * settings.sfx_level())
+                .with_speed(speed),
+        );
+        voices.push_back(now);
+    }
+}
+
+//plays the sound effects for the gameplay events
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SoundLibrary>()
+            .add_startup_system(load_sounds)
+            .add_system(play_sounds);
+    }
+}
+
+//system for loading every sound once at startup, instead of on every play
+pub fn load_sounds(asset_server: Res<AssetServer>, mut sound_library: ResMut<SoundLibrary>) {
+    for kind in SoundKind::ALL {
+        let handles = kind
+            .paths()
+            .iter()
+            .map(|path| asset_server.load(*path))
+            .collect();
+        sound_library.handles.insert(kind, handles);
+    }
+}
+
+/*
+ system for turning gameplay events into sounds, with parameter
+ - the event readers for everything that makes a sound
+ - sound_library for the sounds and the voices already playing
+ - settings for the volume
+ - time to know when voices end, real time so it keeps going while paused
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn play_sounds(
+    mut enemy_bounced_event_reader: EventReader<EnemyBounced>,
+    mut star_collected_event_reader: EventReader<StarCollected>,
+    mut grazed_event_reader: EventReader<Grazed>,
+    mut power_up_collected_event_reader: EventReader<PowerUpCollected>,
+    mut player_hit_event_reader: EventReader<PlayerHit>,
+    mut boss_defeated_event_reader: EventReader<BossDefeated>,
+    mut sound_library: ResMut<SoundLibrary>,
+    audio: Res<Audio>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let sounds = [
+        (SoundKind::Bounce, enemy_bounced_event_reader.iter().count()),
+        (
+            SoundKind::StarCollected,
+            star_collected_event_reader.iter().count(),
+        ),
+        (SoundKind::Graze, grazed_event_reader.iter().count()),
+        (
+            SoundKind::PowerUp,
+            power_up_collected_event_reader.iter().count(),
+        ),
+        (SoundKind::PlayerHit, player_hit_event_reader.iter().count()),
+        (
+            SoundKind::BossDefeated,
+            boss_defeated_event_reader.iter().count(),
+        ),
+    ];
+
+    let now = time.raw_elapsed_seconds_f64();
+    for (kind, count) in sounds {
+        //the voice limit would drop the rest anyway
+        for _ in 0..count.min(kind.max_voices()) {
+            sound_library.play(kind, &audio, &settings, now);
+        }
+    }
+}
@@ -1,15 +1,635 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
+use rand::prelude::*;
+
+pub const PLAYER_TEXTURE: &str = "sprites/ball_blue_large.png";
 
 //entity player
-#[derive(Component)]
-pub struct Player {}
+#[derive(Component, Default)]
+pub struct Player {
+    pub hit: bool, //set on the first fatal hit, so a second one before the despawn is applied doesn't end the run again
+}
+
+//player velocity, used by the inertial movement model
+#[derive(Component, Default)]
+pub struct Velocity {
+    pub value: Vec2,
+}
+
+//enemy behaviour variable
+pub const ENEMY_SIZE: f32 = 64.0; //enemy sprite size
+pub const BOUNCER_SPEED: f32 = 200.0;
+pub const CHASER_SPEED: f32 = 150.0;
+pub const WANDERER_SPEED: f32 = 180.0;
+pub const ORBITER_SPEED: f32 = 220.0;
+pub const SWARM_SPEED: f32 = 170.0;
+pub const SWARM_MEMBER_SIZE: f32 = 32.0; //swarm members are drawn at half the size of the other enemies
+pub const BOSS_SPEED: f32 = 120.0;
+pub const BOSS_SIZE: f32 = 160.0;
+
+//steering behaviour of an enemy
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnemyKind {
+    Bouncer,  //moves in a straight line and bounces off the edges
+    Chaser,   //homes in on the player with a limited turn rate
+    Wanderer, //random walk
+    Orbiter,  //circles around its anchor point
+    Swarm,    //flocks with the other swarm members
+    Boss,     //moves according to its Boss phase
+}
+
+impl EnemyKind {
+    pub fn speed(&self) -> f32 {
+        match self {
+            EnemyKind::Bouncer => BOUNCER_SPEED,
+            EnemyKind::Chaser => CHASER_SPEED,
+            EnemyKind::Wanderer => WANDERER_SPEED,
+            EnemyKind::Orbiter => ORBITER_SPEED,
+            EnemyKind::Swarm => SWARM_SPEED,
+            EnemyKind::Boss => BOSS_SPEED,
+        }
+    }
+
+    //sprite size, also used as the collision diameter
+    pub fn size(&self) -> f32 {
+        match self {
+            EnemyKind::Swarm => SWARM_MEMBER_SIZE,
+            EnemyKind::Boss => BOSS_SIZE,
+            _ => ENEMY_SIZE,
+        }
+    }
+
+    pub fn texture(&self) -> &'static str {
+        match self {
+            EnemyKind::Bouncer => "sprites/ball_red_large.png",
+            EnemyKind::Chaser => "sprites/ball_purple_large.png",
+            EnemyKind::Wanderer => "sprites/ball_green_large.png",
+            EnemyKind::Orbiter => "sprites/ball_yellow_large.png",
+            EnemyKind::Swarm => "sprites/ball_orange_large.png",
+            EnemyKind::Boss => "sprites/ball_red_large.png",
+        }
+    }
+
+    //bullet pattern this kind fires, if any
+    pub fn fire_pattern(&self) -> Option<FirePattern> {
+        match self {
+            EnemyKind::Chaser => Some(FirePattern {
+                pattern: BulletPattern::Aimed {
+                    count: 1,
+                    spread: 0.0,
+                },
+                interval: 2.5,
+                speed: 260.0,
+            }),
+            EnemyKind::Orbiter => Some(FirePattern {
+                pattern: BulletPattern::Radial { count: 8 },
+                interval: 4.0,
+                speed: 180.0,
+            }),
+            _ => None,
+        }
+    }
+}
+
+//shape of a volley of projectiles
+#[derive(Clone, Copy, Debug)]
+pub enum BulletPattern {
+    Aimed { count: u32, spread: f32 }, //fan of `count` shots centred on the player, `spread` radians apart
+    Radial { count: u32 },             //`count` shots evenly around the shooter
+    Spiral { arms: u32, step: f32 },   //evenly spaced arms, rotating `step` radians every volley
+}
+
+//data describing how an enemy shoots
+#[derive(Clone, Copy, Debug)]
+pub struct FirePattern {
+    pub pattern: BulletPattern,
+    pub interval: f32, //seconds between volleys
+    pub speed: f32,    //projectile speed
+}
+
+impl FirePattern {
+    //directions of every projectile in one volley, `aim` points at the player and `angle` is the spiral rotation
+    pub fn directions(&self, aim: Vec2, angle: f32) -> Vec<Vec2> {
+        match self.pattern {
+            BulletPattern::Aimed { count, spread } => {
+                let first = -(count.saturating_sub(1) as f32) * spread / 2.0;
+                (0..count)
+                    .map(|i| Vec2::from_angle(first + i as f32 * spread).rotate(aim))
+                    .collect()
+            }
+            BulletPattern::Radial { count } => (0..count)
+                .map(|i| Vec2::from_angle(i as f32 * std::f32::consts::TAU / count as f32))
+                .collect(),
+            BulletPattern::Spiral { arms, .. } => (0..arms)
+                .map(|i| Vec2::from_angle(angle + i as f32 * std::f32::consts::TAU / arms as f32))
+                .collect(),
+        }
+    }
+}
 
 //entity enemy
 #[derive(Component)]
 pub struct Enemy {
-    pub direction: Vec2, //keeping track of enemy's direction
+    pub kind: EnemyKind,
+    pub direction: Vec2,     //keeping track of enemy's direction
+    pub anchor: Vec2,        //point an orbiter circles around
+    pub grazing: bool,       //whether the enemy is inside the player's graze ring
+    pub graze_cooldown: f32, //seconds before this enemy can award another graze bonus
+    pub fire_cooldown: f32,  //seconds before the next volley
+    pub fire_angle: f32,     //current rotation of spiral patterns
+}
+
+impl Enemy {
+    pub fn new(kind: EnemyKind, direction: Vec2) -> Enemy {
+        Enemy {
+            kind,
+            direction,
+            anchor: Vec2::ZERO,
+            grazing: false,
+            graze_cooldown: 0.0,
+            fire_cooldown: kind.fire_pattern().map_or(0.0, |pattern| pattern.interval),
+            fire_angle: 0.0,
+        }
+    }
+}
+
+//component projectile fired by an enemy, hurts the player like an enemy does
+#[derive(Component)]
+pub struct Projectile {
+    pub velocity: Vec2,
+}
+
+//star variable
+pub const GOLDEN_STAR_VALUE: u32 = 5;
+pub const DRIFTING_STAR_SPEED: f32 = 80.0;
+pub const FADING_STAR_LIFETIME: f32 = 6.0; //seconds before a fading star despawns
+
+//kinds of star
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StarKind {
+    Common,
+    Golden,
+    Drifting,
+    Fading,
+}
+
+impl StarKind {
+    //tint applied to the star sprite
+    pub fn color(&self) -> Color {
+        match self {
+            StarKind::Common => Color::WHITE,
+            StarKind::Golden => Color::GOLD,
+            StarKind::Drifting => Color::rgb(0.6, 0.9, 1.0),
+            StarKind::Fading => Color::rgb(1.0, 0.7, 0.9),
+        }
+    }
 }
 
 //component star
 #[derive(Component)]
-pub struct Star {}
+pub struct Star {
+    pub kind: StarKind,
+    pub value: u32,              //points given when collected
+    pub velocity: Vec2,          //zero for stars that don't move
+    pub lifetime: Option<Timer>, //stars with a lifetime despawn when it runs out
+}
+
+impl Star {
+    //build a star with the default fields for its kind
+    pub fn new(kind: StarKind) -> Star {
+        let mut star = Star {
+            kind,
+            value: 1,
+            velocity: Vec2::ZERO,
+            lifetime: None,
+        };
+        match kind {
+            StarKind::Common => {}
+            StarKind::Golden => star.value = GOLDEN_STAR_VALUE,
+            StarKind::Drifting => {
+                let angle = random::<f32>() * std::f32::consts::TAU;
+                star.velocity = Vec2::from_angle(angle) * DRIFTING_STAR_SPEED;
+            }
+            StarKind::Fading => {
+                star.lifetime = Some(Timer::from_seconds(FADING_STAR_LIFETIME, TimerMode::Once))
+            }
+        }
+        star
+    }
+}
+
+//power-up effect durations in seconds
+pub const SHIELD_DURATION: f32 = 5.0;
+pub const SPEED_BOOST_DURATION: f32 = 6.0;
+pub const SLOW_MOTION_DURATION: f32 = 4.0;
+pub const STAR_MAGNET_DURATION: f32 = 8.0;
+pub const SCORE_MULTIPLIER_DURATION: f32 = 10.0;
+pub const MAX_EFFECT_STACKS: u32 = 3; //stack limit for stackable effects
+
+//kinds of power-up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerUpKind {
+    Shield,
+    SpeedBoost,
+    SlowMotion,
+    StarMagnet,
+    ScoreMultiplier,
+}
+
+impl PowerUpKind {
+    //how long the effect lasts once picked up
+    pub fn duration(&self) -> f32 {
+        match self {
+            PowerUpKind::Shield => SHIELD_DURATION,
+            PowerUpKind::SpeedBoost => SPEED_BOOST_DURATION,
+            PowerUpKind::SlowMotion => SLOW_MOTION_DURATION,
+            PowerUpKind::StarMagnet => STAR_MAGNET_DURATION,
+            PowerUpKind::ScoreMultiplier => SCORE_MULTIPLIER_DURATION,
+        }
+    }
+
+    //stackable effects gain a stack on pickup, the others only refresh their duration
+    pub fn stacks(&self) -> bool {
+        matches!(self, PowerUpKind::ScoreMultiplier)
+    }
+
+    //colour of the pickup sprite and HUD text
+    pub fn color(&self) -> Color {
+        match self {
+            PowerUpKind::Shield => Color::CYAN,
+            PowerUpKind::SpeedBoost => Color::ORANGE,
+            PowerUpKind::SlowMotion => Color::PURPLE,
+            PowerUpKind::StarMagnet => Color::GREEN,
+            PowerUpKind::ScoreMultiplier => Color::GOLD,
+        }
+    }
+
+    //name shown on the HUD
+    pub fn label(&self) -> &'static str {
+        match self {
+            PowerUpKind::Shield => "Shield",
+            PowerUpKind::SpeedBoost => "Speed",
+            PowerUpKind::SlowMotion => "Slow-mo",
+            PowerUpKind::StarMagnet => "Magnet",
+            PowerUpKind::ScoreMultiplier => "Score x",
+        }
+    }
+}
+
+//component power-up, picked up by the player
+#[derive(Component)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+}
+
+//a single effect currently active on the player
+pub struct ActiveEffect {
+    pub kind: PowerUpKind,
+    pub stacks: u32,
+    pub timer: Timer, //remaining duration
+}
+
+//component on the player, keeping track of every active effect
+#[derive(Component, Default)]
+pub struct ActiveEffects {
+    pub effects: Vec<ActiveEffect>,
+}
+
+impl ActiveEffects {
+    //add a new effect, or stack/refresh an existing one
+    pub fn apply(&mut self, kind: PowerUpKind) {
+        if let Some(effect) = self.effects.iter_mut().find(|effect| effect.kind == kind) {
+            if kind.stacks() && effect.stacks < MAX_EFFECT_STACKS {
+                effect.stacks += 1;
+            }
+            effect.timer.reset();
+        } else {
+            self.effects.push(ActiveEffect {
+                kind,
+                stacks: 1,
+                timer: Timer::from_seconds(kind.duration(), TimerMode::Once),
+            });
+        }
+    }
+
+    //tick every effect and drop the ones that expired
+    pub fn tick(&mut self, delta: Duration) {
+        for effect in self.effects.iter_mut() {
+            effect.timer.tick(delta);
+        }
+        self.effects.retain(|effect| !effect.timer.finished());
+    }
+
+    pub fn is_active(&self, kind: PowerUpKind) -> bool {
+        self.stacks(kind) > 0
+    }
+
+    //number of stacks, 0 when the effect is not active
+    pub fn stacks(&self, kind: PowerUpKind) -> u32 {
+        self.effects
+            .iter()
+            .find(|effect| effect.kind == kind)
+            .map_or(0, |effect| effect.stacks)
+    }
+}
+
+//HUD text listing the active effects and their remaining time
+#[derive(Component)]
+pub struct EffectsText {}
+
+//HUD text showing entity counts
+#[derive(Component)]
+pub struct MetricsText {}
+
+//HUD text showing the score and the current combo
+#[derive(Component)]
+pub struct ScoreText {}
+
+//root node of the game-over screen
+#[derive(Component)]
+pub struct GameOverScreen {}
+
+//short-lived text that floats up and fades out, like the graze bonus popup
+#[derive(Component)]
+pub struct FloatingText {
+    pub timer: Timer,
+}
+
+pub const BOSS_HEALTH: f32 = 30.0;
+pub const BOSS_MINION_TIME: f32 = 6.0; //seconds between minion spawns in the last phase
+
+//boss phases, the boss gets more aggressive as it loses health
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BossPhase {
+    Drifting, //bounces around slowly
+    Hunting,  //chases the player
+    Enraged,  //chases faster and keeps calling minions
+}
+
+impl BossPhase {
+    //phase for the given fraction of health left
+    pub fn from_health(health_fraction: f32) -> BossPhase {
+        if health_fraction > 2.0 / 3.0 {
+            BossPhase::Drifting
+        } else if health_fraction > 1.0 / 3.0 {
+            BossPhase::Hunting
+        } else {
+            BossPhase::Enraged
+        }
+    }
+
+    pub fn speed_factor(&self) -> f32 {
+        match self {
+            BossPhase::Drifting => 1.0,
+            BossPhase::Hunting => 1.3,
+            BossPhase::Enraged => 1.8,
+        }
+    }
+
+    //the boss fires a different pattern in each phase
+    pub fn fire_pattern(&self) -> FirePattern {
+        match self {
+            BossPhase::Drifting => FirePattern {
+                pattern: BulletPattern::Aimed {
+                    count: 3,
+                    spread: 0.3,
+                },
+                interval: 2.0,
+                speed: 240.0,
+            },
+            BossPhase::Hunting => FirePattern {
+                pattern: BulletPattern::Radial { count: 12 },
+                interval: 2.5,
+                speed: 200.0,
+            },
+            BossPhase::Enraged => FirePattern {
+                pattern: BulletPattern::Spiral {
+                    arms: 3,
+                    step: 0.35,
+                },
+                interval: 0.2,
+                speed: 220.0,
+            },
+        }
+    }
+}
+
+//component boss, lives on an enemy entity of kind Boss
+#[derive(Component)]
+pub struct Boss {
+    pub health: f32,
+    pub max_health: f32,
+    pub phase: BossPhase,
+    pub minion_timer: Timer,
+}
+
+impl Default for Boss {
+    fn default() -> Boss {
+        Boss {
+            health: BOSS_HEALTH,
+            max_health: BOSS_HEALTH,
+            phase: BossPhase::Drifting,
+            minion_timer: Timer::from_seconds(BOSS_MINION_TIME, TimerMode::Repeating),
+        }
+    }
+}
+
+//root node of the boss health bar
+#[derive(Component)]
+pub struct BossHealthBar {}
+
+//inner node of the boss health bar, its width follows the boss's health
+#[derive(Component)]
+pub struct BossHealthFill {}
+
+pub const DASH_DURATION: f32 = 0.18;
+pub const DASH_INVULNERABILITY: f32 = 0.3; //i-frames last a little longer than the dash itself
+pub const DASH_COOLDOWN: f32 = 1.5;
+
+//component dash on the player
+#[derive(Component)]
+pub struct Dash {
+    pub direction: Vec2,      //direction of the current dash
+    pub last_direction: Vec2, //last direction the player moved in, used when dashing
+    pub time_left: f32,
+    pub invulnerable_left: f32,
+    pub cooldown_left: f32,
+}
+
+impl Default for Dash {
+    fn default() -> Dash {
+        Dash {
+            direction: Vec2::ZERO,
+            last_direction: Vec2::X,
+            time_left: 0.0,
+            invulnerable_left: 0.0,
+            cooldown_left: 0.0,
+        }
+    }
+}
+
+impl Dash {
+    pub fn is_ready(&self) -> bool {
+        self.cooldown_left <= 0.0
+    }
+
+    pub fn is_dashing(&self) -> bool {
+        self.time_left > 0.0
+    }
+
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerable_left > 0.0
+    }
+
+    //dash in the last direction the player moved in
+    pub fn start(&mut self) {
+        self.direction = self.last_direction;
+        self.time_left = DASH_DURATION;
+        self.invulnerable_left = DASH_INVULNERABILITY;
+        self.cooldown_left = DASH_COOLDOWN;
+    }
+
+    pub fn tick(&mut self, delta: f32) {
+        self.time_left = (self.time_left - delta).max(0.0);
+        self.invulnerable_left = (self.invulnerable_left - delta).max(0.0);
+        self.cooldown_left = (self.cooldown_left - delta).max(0.0);
+    }
+}
+
+//faded copy of the player sprite left behind while dashing
+#[derive(Component)]
+pub struct Afterimage {
+    pub timer: Timer,
+}
+
+//HUD text showing the dash cooldown
+#[derive(Component)]
+pub struct DashText {}
+
+//shape of a wall, centred on the wall's transform
+#[derive(Clone, Copy, Debug)]
+pub enum WallShape {
+    Rect { half_size: Vec2 },
+    Circle { radius: f32 },
+}
+
+impl WallShape {
+    /*
+     circle collision against this shape placed at `center`,
+     returns the push-out normal and penetration depth if the circle overlaps the shape
+    */
+    pub fn collide_circle(&self, center: Vec2, position: Vec2, radius: f32) -> Option<(Vec2, f32)> {
+        match *self {
+            WallShape::Circle {
+                radius: wall_radius,
+            } => {
+                let offset = position - center;
+                let distance = offset.length();
+                let depth = radius + wall_radius - distance;
+                if depth <= 0.0 {
+                    return None;
+                }
+                let normal = if distance > 0.0 {
+                    offset / distance
+                } else {
+                    Vec2::Y
+                };
+                Some((normal, depth))
+            }
+            WallShape::Rect { half_size } => {
+                let local = position - center;
+                let closest = local.clamp(-half_size, half_size);
+
+                if closest != local {
+                    //circle center outside the box, push away from the closest point
+                    let offset = local - closest;
+                    let distance = offset.length();
+                    let depth = radius - distance;
+                    if depth <= 0.0 {
+                        return None;
+                    }
+                    return Some((offset / distance, depth));
+                }
+
+                //circle center inside the box, push out along the axis with the least penetration
+                let to_edge = half_size - local.abs();
+                if to_edge.x < to_edge.y {
+                    Some((Vec2::new(local.x.signum(), 0.0), to_edge.x + radius))
+                } else {
+                    Some((Vec2::new(0.0, local.y.signum()), to_edge.y + radius))
+                }
+            }
+        }
+    }
+}
+
+//component wall, static obstacle blocking the player and reflecting enemies
+#[derive(Component)]
+pub struct Wall {
+    pub shape: WallShape,
+}
+
+//sprite drawn by the arena editor (grid, spawn regions, player start, selection), rebuilt on every edit
+#[derive(Component)]
+pub struct EditorVisual {}
+
+//HUD text showing the editor tool and key bindings
+#[derive(Component)]
+pub struct EditorText {}
+
+//copy of a sprite drawn one arena width or height away, so sprites crossing the seam of a wrapping arena show on both sides
+#[derive(Component)]
+pub struct WrapGhost {
+    pub axes: Vec2, //axes the copy is shifted along, (1, 0), (0, 1) or (1, 1)
+}
+
+//marker for sprites that already have their wrap ghosts as children
+#[derive(Component)]
+pub struct WrapGhosts {}
+
+//arena background entities (the floor of a circular arena and the background image), rebuilt with the walls
+#[derive(Component)]
+pub struct ArenaFloor {}
+
+//arrow at the edge of the screen pointing at an enemy or star that is off screen
+#[derive(Component)]
+pub struct OffscreenIndicator {}
+
+//corner minimap showing the whole arena
+#[derive(Component)]
+pub struct Minimap {}
+
+//dot on the minimap for the player, an enemy or a star
+#[derive(Component)]
+pub struct MinimapMarker {}
+
+//rectangle on the minimap showing what the camera sees
+#[derive(Component)]
+pub struct MinimapViewport {}
+
+//camera that shows the arena, as opposed to the one that only draws the UI
+#[derive(Component)]
+pub struct GameCamera {}
+
+//pause menu overlay
+#[derive(Component)]
+pub struct PauseMenu {}
+
+//settings menu overlay
+#[derive(Component)]
+pub struct SettingsMenu {}
+
+//entry of the open menu, index is its position in the menu
+#[derive(Component)]
+pub struct MenuButton {
+    pub index: usize,
+}
+
+//big number counting down before play continues
+#[derive(Component)]
+pub struct CountdownText {}
+
+//quit confirmation overlay
+#[derive(Component)]
+pub struct ConfirmQuitMenu {}
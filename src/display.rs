@@ -0,0 +1,162 @@
+use bevy::core_pipeline::clear_color::ClearColorConfig;
+use bevy::render::camera::{ScalingMode, Viewport};
+use bevy::render::view::RenderLayers;
+use bevy::window::{PrimaryWindow, WindowMode, WindowResized};
+
+use bevy::prelude::*;
+
+use crate::arena::Arena;
+use crate::components::*;
+use crate::resources::Settings;
+use crate::states::AppState;
+use crate::systems::CAMERA_Z;
+
+//the game always shows this much of the world, whatever the window size
+pub const LOGICAL_WIDTH: f32 = 1280.0;
+pub const LOGICAL_HEIGHT: f32 = 720.0;
+pub const LETTERBOX_COLOR: Color = Color::BLACK; //bars around the view when the window has a different aspect ratio
+pub const FULLSCREEN_KEY: KeyCode = KeyCode::F11;
+pub const UI_RENDER_LAYER: u8 = 1; //nothing but the UI camera is on this layer, so it draws no sprites
+
+/*
+ system for spawning the cameras, with parameter
+ - commands untuk memberikan command,
+ - arena to get the player start
+
+ the game camera always shows LOGICAL_WIDTH x LOGICAL_HEIGHT of the world inside a letterboxed viewport,
+ the UI camera draws the HUD over the whole window so it isn't clipped by the letterbox
+*/
+pub fn spawn_camera(mut commands: Commands, arena: Res<Arena>) {
+    let mut camera = Camera2dBundle {
+        transform: Transform::from_translation(arena.player_start.extend(CAMERA_Z)), //start on the player, camera_follow keeps it inside the arena
+        ..default()
+    };
+    camera.projection.scaling_mode = ScalingMode::Fixed {
+        width: LOGICAL_WIDTH,
+        height: LOGICAL_HEIGHT,
+    };
+    commands.spawn((camera, UiCameraConfig { show_ui: false }, GameCamera {}));
+
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                order: 1,
+                ..default()
+            },
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::None,
+            },
+            ..default()
+        },
+        RenderLayers::layer(UI_RENDER_LAYER),
+    ));
+}
+
+//biggest viewport with the logical aspect ratio that fits in the window, centred
+fn letterbox_viewport(window_size: UVec2) -> Viewport {
+    let window = window_size.as_vec2();
+    let scale = (window.x / LOGICAL_WIDTH).min(window.y / LOGICAL_HEIGHT);
+    let size = (Vec2::new(LOGICAL_WIDTH, LOGICAL_HEIGHT) * scale)
+        .as_uvec2()
+        .max(UVec2::ONE)
+        .min(window_size.max(UVec2::ONE));
+    Viewport {
+        physical_position: (window_size.max(size) - size) / 2,
+        physical_size: size,
+        ..default()
+    }
+}
+
+/*
+ system for fitting the game camera to the window when it is resized, with parameter
+ - resize_events to know when the window changed size
+ - window_query for the new size
+ - camera_query to letterbox the game camera and re-centre it
+ - player_query to re-centre on the player
+ - arena to keep the view inside the world bounds
+ - app_state to leave the camera where it is while editing
+*/
+pub fn fit_camera_to_window(
+    mut resize_events: EventReader<WindowResized>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut camera_query: Query<
+        (&mut Camera, &mut Transform, &OrthographicProjection),
+        With<GameCamera>,
+    >,
+    player_query: Query<&Transform, (With<Player>, Without<GameCamera>)>,
+    arena: Res<Arena>,
+    app_state: Res<State<AppState>>,
+) {
+    let resized = resize_events.iter().count() > 0;
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let Ok((mut camera, mut camera_transform, projection)) = camera_query.get_single_mut() else {
+        return;
+    };
+    //the first frame has no resize event, but the camera still needs its viewport
+    if !resized && camera.viewport.is_some() {
+        return;
+    }
+
+    let window_size = UVec2::new(window.physical_width(), window.physical_height());
+    if window_size.cmpeq(UVec2::ZERO).any() {
+        return; //minimised
+    }
+    camera.viewport = Some(letterbox_viewport(window_size));
+
+    if app_state.0 == AppState::Game {
+        let target = player_query
+            .get_single()
+            .map(|transform| transform.translation.truncate())
+            .unwrap_or(camera_transform.translation.truncate());
+        let position = arena.clamp_view(target, projection.area.size() / 2.0);
+        camera_transform.translation = position.extend(camera_transform.translation.z);
+    }
+}
+
+//system for switching between windowed and borderless fullscreen with the fullscreen key
+pub fn toggle_fullscreen(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+    if keyboard_input.just_pressed(FULLSCREEN_KEY) {
+        settings.window_mode = match settings.window_mode {
+            WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+            _ => WindowMode::Windowed,
+        };
+    }
+}
+
+//system for giving the window the mode and vsync from the settings
+pub fn apply_window_settings(
+    settings: Res<Settings>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    if let Ok(mut window) = window_query.get_single_mut() {
+        //only touch the window when something changed, setting it at all makes the window backend update
+        if window.mode != settings.window_mode {
+            window.mode = settings.window_mode;
+        }
+        if window.present_mode != settings.present_mode() {
+            window.present_mode = settings.present_mode();
+        }
+    }
+}
+
+/*
+ function for turning the cursor into a world position with the game camera, with parameter
+ - window for the cursor position, measured from the bottom left of the window
+ - camera and camera_transform of the letterboxed game camera
+*/
+pub fn cursor_to_world(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    let cursor = window.cursor_position()?;
+    //the viewport rect is measured from the top left, the cursor from the bottom left
+    let (min, max) = camera.logical_viewport_rect()?;
+    let viewport_cursor = Vec2::new(cursor.x - min.x, cursor.y - (window.height() - max.y));
+    camera.viewport_to_world_2d(camera_transform, viewport_cursor)
+}
@@ -0,0 +1,620 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+use crate::arena::*;
+use crate::components::*;
+use crate::display::cursor_to_world;
+use crate::hud::{HUD_FONT, HUD_FONT_SIZE};
+use crate::resources::*;
+use crate::states::AppState;
+use crate::systems::{spawn_arena_floor, spawn_wall, PLAYER_SIZE};
+
+pub const EDITOR_KEY: KeyCode = KeyCode::F1;
+pub const GRID_SIZE: f32 = 20.0;
+pub const MIN_EDITOR_SIZE: f32 = 20.0; //smallest width, height or diameter the editor makes
+pub const GRID_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.08);
+pub const ENEMY_REGION_COLOR: Color = Color::rgba(1.0, 0.2, 0.2, 0.25);
+pub const STAR_REGION_COLOR: Color = Color::rgba(1.0, 0.9, 0.2, 0.25);
+pub const SELECTION_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.35);
+pub const EDITOR_PAN_SPEED: f32 = 800.0;
+pub const EDITOR_HELP: &str =
+    "Arrows pan | 1-5 tool | drag to place, move | Shift+drag resize | Del delete | G snap | B boundary | Ctrl+Z/Y undo/redo | Ctrl+S save | F1 play";
+
+//everything the game shows that the editor hides while it is open
+type GameplayFilter = Or<(
+    With<Player>,
+    With<Enemy>,
+    With<Star>,
+    With<PowerUp>,
+    With<Projectile>,
+    With<Afterimage>,
+    With<FloatingText>,
+    With<OffscreenIndicator>,
+)>;
+
+//everything the editor spawns
+type EditorFilter = Or<(With<EditorVisual>, With<EditorText>)>;
+
+//everything respawn_arena rebuilds
+type ArenaFilter = Or<(With<Wall>, With<ArenaFloor>)>;
+
+//system for switching between playing and editing the arena
+pub fn toggle_editor(
+    keyboard_input: Res<Input<KeyCode>>,
+    app_state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if keyboard_input.just_pressed(EDITOR_KEY) {
+        match app_state.0 {
+            AppState::Game => next_state.set(AppState::Editor),
+            AppState::Editor => next_state.set(AppState::Game),
+            _ => {}
+        }
+    }
+}
+
+/*
+ system for opening the editor, with parameter
+ - gameplay_query to hide the frozen game
+ - editor_state to forget the previous selection
+*/
+pub fn enter_editor(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut gameplay_query: Query<&mut Visibility, GameplayFilter>,
+    mut editor_state: ResMut<EditorState>,
+) {
+    for mut visibility in gameplay_query.iter_mut() {
+        *visibility = Visibility::Hidden;
+    }
+    editor_state.selected = None;
+    editor_state.drag = None;
+    editor_state.message.clear();
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load(HUD_FONT),
+                font_size: HUD_FONT_SIZE,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(10.0),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+        EditorText {},
+    ));
+}
+
+//system for closing the editor, removing its sprites and showing the game again
+pub fn exit_editor(
+    mut commands: Commands,
+    mut gameplay_query: Query<&mut Visibility, GameplayFilter>,
+    editor_query: Query<Entity, EditorFilter>,
+) {
+    for mut visibility in gameplay_query.iter_mut() {
+        *visibility = Visibility::Inherited;
+    }
+    for entity in editor_query.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+/*
+ system for the editor keyboard shortcuts, with parameter
+ - keyboard_input to read the shortcuts
+ - arena to edit, undo and save
+ - arena_file for the path to save to
+ - editor_state for the tool, snapping, selection and history
+*/
+pub fn editor_keyboard(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut arena: ResMut<Arena>,
+    arena_file: Res<ArenaFile>,
+    mut editor_state: ResMut<EditorState>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+    let shift = keyboard_input.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+
+    let tools = [
+        (KeyCode::Key1, EditorTool::RectWall),
+        (KeyCode::Key2, EditorTool::CircleWall),
+        (KeyCode::Key3, EditorTool::EnemyRegion),
+        (KeyCode::Key4, EditorTool::StarRegion),
+        (KeyCode::Key5, EditorTool::PlayerStart),
+    ];
+    for (key, tool) in tools {
+        if keyboard_input.just_pressed(key) {
+            editor_state.tool = tool;
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::G) {
+        editor_state.snap = !editor_state.snap;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::B) {
+        let before = arena.clone();
+        arena.boundary = arena.boundary.next();
+        editor_state.record(before);
+    }
+
+    if keyboard_input.any_just_pressed([KeyCode::Delete, KeyCode::Back]) {
+        if let Some(item) = editor_state.selected {
+            let before = arena.clone();
+            if remove_item(&mut arena, item) {
+                //a drag in progress ends here, undoing goes back to before it started
+                let before = editor_state.before_drag.take().unwrap_or(before);
+                editor_state.record(before);
+                editor_state.selected = None;
+                editor_state.cancel_drag();
+            }
+        }
+    }
+
+    if ctrl && keyboard_input.just_pressed(KeyCode::Z) {
+        if shift {
+            editor_state.redo(&mut arena);
+        } else {
+            editor_state.undo(&mut arena);
+        }
+    }
+    if ctrl && keyboard_input.just_pressed(KeyCode::Y) {
+        editor_state.redo(&mut arena);
+    }
+
+    if ctrl && keyboard_input.just_pressed(KeyCode::S) {
+        editor_state.message = match arena.save(&arena_file.path) {
+            Ok(()) => format!("Saved {}", arena_file.path),
+            Err(error) => format!("Not saved: {}", error),
+        };
+        println!("{}", editor_state.message);
+    }
+}
+
+/*
+ system for scrolling around arenas bigger than the screen, with parameter
+ - keyboard_input to read the arrow keys
+ - camera_query to move the camera
+ - arena to keep the view inside the world bounds
+*/
+pub fn editor_pan_camera(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut camera_query: Query<(&mut Transform, &OrthographicProjection), With<GameCamera>>,
+    arena: Res<Arena>,
+    time: Res<Time>,
+) {
+    let Ok((mut camera_transform, projection)) = camera_query.get_single_mut() else {
+        return;
+    };
+
+    let mut direction = Vec2::ZERO;
+    if keyboard_input.pressed(KeyCode::Left) {
+        direction.x -= 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::Right) {
+        direction.x += 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::Up) {
+        direction.y += 1.0;
+    }
+    if keyboard_input.pressed(KeyCode::Down) {
+        direction.y -= 1.0;
+    }
+    if direction == Vec2::ZERO {
+        return;
+    }
+
+    let position = camera_transform.translation.truncate()
+        + direction.normalize() * EDITOR_PAN_SPEED * time.delta_seconds();
+    let position = arena.clamp_view(position, projection.area.size() / 2.0);
+    camera_transform.translation = position.extend(camera_transform.translation.z);
+}
+
+/*
+ system for placing, moving and resizing things with the mouse, with parameter
+ - window_query and camera_query to turn the cursor into a world position
+ - mouse_input to know when the left button is pressed, held and released
+ - keyboard_input to resize instead of move while shift is held
+ - arena to edit
+ - editor_state for the tool, snapping, selection and the current drag
+*/
+pub fn editor_mouse(
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<GameCamera>>,
+    mouse_input: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut arena: ResMut<Arena>,
+    mut editor_state: ResMut<EditorState>,
+) {
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = camera_query.get_single() else {
+        return;
+    };
+    let Some(cursor) = cursor_to_world(window, camera, camera_transform) else {
+        return;
+    };
+    let point = if editor_state.snap {
+        (cursor / GRID_SIZE).round() * GRID_SIZE
+    } else {
+        cursor
+    };
+
+    if mouse_input.just_pressed(MouseButton::Left) {
+        editor_state.before_drag = Some(arena.clone());
+
+        let drag = match pick_item(&arena, cursor) {
+            Some(item) => {
+                let shift = keyboard_input.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+                match resize_anchor(&arena, item, cursor) {
+                    Some(anchor) if shift => EditorDrag::Resize { item, anchor },
+                    _ => EditorDrag::Move { item, last: point },
+                }
+            }
+            //nothing under the cursor, place something new with the current tool
+            None => {
+                let item = add_item(&mut arena, editor_state.tool, point);
+                match item {
+                    EditorItem::PlayerStart => EditorDrag::Move { item, last: point },
+                    _ => EditorDrag::Resize {
+                        item,
+                        anchor: point,
+                    },
+                }
+            }
+        };
+        editor_state.selected = Some(match drag {
+            EditorDrag::Move { item, .. } => item,
+            EditorDrag::Resize { item, .. } => item,
+        });
+        editor_state.drag = Some(drag);
+    }
+
+    if mouse_input.pressed(MouseButton::Left) {
+        match editor_state.drag {
+            Some(EditorDrag::Move { item, last }) if point != last => {
+                move_item(&mut arena, item, point - last);
+                editor_state.drag = Some(EditorDrag::Move { item, last: point });
+            }
+            Some(EditorDrag::Resize { item, anchor }) => {
+                let mut resized = arena.clone();
+                resize_item(&mut resized, item, anchor, point);
+                if resized != *arena {
+                    *arena = resized;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if mouse_input.just_released(MouseButton::Left) {
+        editor_state.drag = None;
+        if let Some(before) = editor_state.before_drag.take() {
+            if before != *arena {
+                editor_state.record(before);
+            }
+        }
+    }
+}
+
+//find the item under the cursor, the player start first and then the most recently added
+fn pick_item(arena: &Arena, point: Vec2) -> Option<EditorItem> {
+    if arena.player_start.distance(point) <= PLAYER_SIZE / 2.0 {
+        return Some(EditorItem::PlayerStart);
+    }
+    let wall = arena.walls.iter().rposition(|wall| {
+        wall.shape()
+            .collide_circle(wall.center(), point, 0.0)
+            .is_some()
+    });
+    if let Some(index) = wall {
+        return Some(EditorItem::Wall(index));
+    }
+    let inside =
+        |region: &SpawnRegion| point.cmpge(region.min).all() && point.cmple(region.max).all();
+    if let Some(index) = arena.enemy_spawn_regions.iter().rposition(inside) {
+        return Some(EditorItem::EnemyRegion(index));
+    }
+    if let Some(index) = arena.star_spawn_regions.iter().rposition(inside) {
+        return Some(EditorItem::StarRegion(index));
+    }
+    None
+}
+
+//add a new, smallest size item of the tool's kind at point and return it
+fn add_item(arena: &mut Arena, tool: EditorTool, point: Vec2) -> EditorItem {
+    let region = SpawnRegion {
+        min: point,
+        max: point + Vec2::splat(MIN_EDITOR_SIZE),
+    };
+    match tool {
+        EditorTool::RectWall => {
+            arena.walls.push(WallLayout::Rect {
+                center: point + Vec2::splat(MIN_EDITOR_SIZE / 2.0),
+                size: Vec2::splat(MIN_EDITOR_SIZE),
+            });
+            EditorItem::Wall(arena.walls.len() - 1)
+        }
+        EditorTool::CircleWall => {
+            arena.walls.push(WallLayout::Circle {
+                center: point,
+                radius: MIN_EDITOR_SIZE / 2.0,
+            });
+            EditorItem::Wall(arena.walls.len() - 1)
+        }
+        EditorTool::EnemyRegion => {
+            arena.enemy_spawn_regions.push(region);
+            EditorItem::EnemyRegion(arena.enemy_spawn_regions.len() - 1)
+        }
+        EditorTool::StarRegion => {
+            arena.star_spawn_regions.push(region);
+            EditorItem::StarRegion(arena.star_spawn_regions.len() - 1)
+        }
+        EditorTool::PlayerStart => {
+            arena.player_start = point;
+            EditorItem::PlayerStart
+        }
+    }
+}
+
+//remove an item, the player start and items that no longer exist can't be removed
+fn remove_item(arena: &mut Arena, item: EditorItem) -> bool {
+    match item {
+        EditorItem::Wall(index) if index < arena.walls.len() => {
+            arena.walls.remove(index);
+        }
+        EditorItem::EnemyRegion(index) if index < arena.enemy_spawn_regions.len() => {
+            arena.enemy_spawn_regions.remove(index);
+        }
+        EditorItem::StarRegion(index) if index < arena.star_spawn_regions.len() => {
+            arena.star_spawn_regions.remove(index);
+        }
+        _ => return false,
+    }
+    true
+}
+
+//items that no longer exist are left alone
+fn move_item(arena: &mut Arena, item: EditorItem, offset: Vec2) {
+    match item {
+        EditorItem::Wall(index) => match arena.walls.get_mut(index) {
+            Some(WallLayout::Rect { center, .. }) => *center += offset,
+            Some(WallLayout::Circle { center, .. }) => *center += offset,
+            None => {}
+        },
+        EditorItem::EnemyRegion(index) => {
+            if let Some(region) = arena.enemy_spawn_regions.get_mut(index) {
+                region.min += offset;
+                region.max += offset;
+            }
+        }
+        EditorItem::StarRegion(index) => {
+            if let Some(region) = arena.star_spawn_regions.get_mut(index) {
+                region.min += offset;
+                region.max += offset;
+            }
+        }
+        EditorItem::PlayerStart => arena.player_start += offset,
+    }
+}
+
+//corner opposite the cursor for boxes, the center for circles, None for the player start and missing items
+fn resize_anchor(arena: &Arena, item: EditorItem, point: Vec2) -> Option<Vec2> {
+    let (min, max) = match item {
+        EditorItem::Wall(index) => match *arena.walls.get(index)? {
+            WallLayout::Rect { center, size } => (center - size / 2.0, center + size / 2.0),
+            WallLayout::Circle { center, .. } => return Some(center),
+        },
+        EditorItem::EnemyRegion(index) => {
+            let region = arena.enemy_spawn_regions.get(index)?;
+            (region.min, region.max)
+        }
+        EditorItem::StarRegion(index) => {
+            let region = arena.star_spawn_regions.get(index)?;
+            (region.min, region.max)
+        }
+        EditorItem::PlayerStart => return None,
+    };
+    let center = (min + max) / 2.0;
+    Some(Vec2::new(
+        if point.x > center.x { min.x } else { max.x },
+        if point.y > center.y { min.y } else { max.y },
+    ))
+}
+
+//stretch a box between the anchor and point, or set a circle's radius to reach point
+fn resize_item(arena: &mut Arena, item: EditorItem, anchor: Vec2, point: Vec2) {
+    let min = anchor.min(point);
+    let max = anchor.max(point).max(min + Vec2::splat(MIN_EDITOR_SIZE));
+    match item {
+        EditorItem::Wall(index) => match arena.walls.get_mut(index) {
+            Some(WallLayout::Rect { center, size }) => {
+                *center = (min + max) / 2.0;
+                *size = max - min;
+            }
+            Some(WallLayout::Circle { radius, .. }) => {
+                *radius = anchor.distance(point).max(MIN_EDITOR_SIZE / 2.0);
+            }
+            None => {}
+        },
+        EditorItem::EnemyRegion(index) => {
+            if let Some(region) = arena.enemy_spawn_regions.get_mut(index) {
+                *region = SpawnRegion { min, max };
+            }
+        }
+        EditorItem::StarRegion(index) => {
+            if let Some(region) = arena.star_spawn_regions.get_mut(index) {
+                *region = SpawnRegion { min, max };
+            }
+        }
+        EditorItem::PlayerStart => {}
+    }
+}
+
+/*
+ system for rebuilding the walls and floor after the arena was edited, with parameter
+ - arena to spawn the walls and floor from
+ - meshes and materials for the floor of a circular arena
+ - arena_query to remove the old walls and floor
+*/
+pub fn respawn_arena(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    arena_query: Query<Entity, ArenaFilter>,
+) {
+    if !arena.is_changed() {
+        return;
+    }
+    for entity in arena_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    spawn_arena_floor(
+        &mut commands,
+        &arena,
+        &asset_server,
+        &mut meshes,
+        &mut materials,
+    );
+    for wall in arena.walls.iter() {
+        spawn_wall(&mut commands, &asset_server, wall.center(), wall.shape());
+    }
+}
+
+/*
+ system for redrawing the grid, spawn regions, player start and selection, with parameter
+ - arena and editor_state to draw from, nothing is redrawn unless one of them changed
+ - visual_query to remove the previous drawing
+*/
+pub fn draw_editor(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    editor_state: Res<EditorState>,
+    asset_server: Res<AssetServer>,
+    visual_query: Query<Entity, With<EditorVisual>>,
+) {
+    if !arena.is_changed() && !editor_state.is_changed() {
+        return;
+    }
+    for entity in visual_query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let mut spawn_box = |min: Vec2, max: Vec2, color: Color, z: f32| {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(max - min),
+                    ..default()
+                },
+                transform: Transform::from_translation(((min + max) / 2.0).extend(z)),
+                ..default()
+            },
+            EditorVisual {},
+        ));
+    };
+
+    //grid lines, only while snapping
+    if editor_state.snap {
+        let mut x = 0.0;
+        while x <= arena.width() {
+            spawn_box(
+                Vec2::new(x - 0.5, 0.0),
+                Vec2::new(x + 0.5, arena.height()),
+                GRID_COLOR,
+                -5.0,
+            );
+            x += GRID_SIZE;
+        }
+        let mut y = 0.0;
+        while y <= arena.height() {
+            spawn_box(
+                Vec2::new(0.0, y - 0.5),
+                Vec2::new(arena.width(), y + 0.5),
+                GRID_COLOR,
+                -5.0,
+            );
+            y += GRID_SIZE;
+        }
+    }
+
+    for region in arena.enemy_spawn_regions.iter() {
+        spawn_box(region.min, region.max, ENEMY_REGION_COLOR, -4.0);
+    }
+    for region in arena.star_spawn_regions.iter() {
+        spawn_box(region.min, region.max, STAR_REGION_COLOR, -4.0);
+    }
+
+    //selection is drawn as a light overlay on top of the selected item
+    let selection = match editor_state.selected {
+        Some(EditorItem::Wall(index)) => arena.walls.get(index).map(|wall| match *wall {
+            WallLayout::Rect { center, size } => (center - size / 2.0, center + size / 2.0),
+            WallLayout::Circle { center, radius } => (center - radius, center + radius),
+        }),
+        Some(EditorItem::EnemyRegion(index)) => arena
+            .enemy_spawn_regions
+            .get(index)
+            .map(|region| (region.min, region.max)),
+        Some(EditorItem::StarRegion(index)) => arena
+            .star_spawn_regions
+            .get(index)
+            .map(|region| (region.min, region.max)),
+        Some(EditorItem::PlayerStart) => Some((
+            arena.player_start - PLAYER_SIZE / 2.0,
+            arena.player_start + PLAYER_SIZE / 2.0,
+        )),
+        None => None,
+    };
+    if let Some((min, max)) = selection {
+        spawn_box(min, max, SELECTION_COLOR, 2.0);
+    }
+
+    //player start marker, a faded player sprite
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::rgba(1.0, 1.0, 1.0, 0.5),
+                ..default()
+            },
+            transform: Transform::from_translation(arena.player_start.extend(1.0)),
+            texture: asset_server.load("sprites/ball_blue_large.png"),
+            ..default()
+        },
+        EditorVisual {},
+    ));
+}
+
+//system for showing the current tool, snapping and last save result
+pub fn update_editor_hud(
+    arena: Res<Arena>,
+    editor_state: Res<EditorState>,
+    mut text_query: Query<&mut Text, With<EditorText>>,
+) {
+    if !arena.is_changed() && !editor_state.is_changed() {
+        return;
+    }
+
+    if let Ok(mut text) = text_query.get_single_mut() {
+        text.sections[0].value = format!(
+            "EDITOR {} | Tool: {} | Boundary: {:?} | Snap: {} | {}\n{}",
+            arena.name,
+            editor_state.tool.label(),
+            arena.boundary,
+            if editor_state.snap { "on" } else { "off" },
+            editor_state.message,
+            EDITOR_HELP,
+        );
+    }
+}
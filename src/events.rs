@@ -1,4 +1,43 @@
+use bevy::prelude::*;
+
+use crate::components::PowerUpKind;
+
 // struct yang akan digunakan untuk event
 pub struct GameOver {
     pub score: u32,
 }
+
+//sent when the player collects a star
+pub struct StarCollected {
+    pub position: Vec3,
+    pub value: u32,
+}
+
+//sent when a boss runs out of health
+pub struct BossDefeated {
+    pub position: Vec3,
+    pub bonus: u32, //score bonus awarded for the kill
+}
+
+//sent when quitting is confirmed, the game saves before it closes
+pub struct Quit {}
+
+//sent when an enemy bounces off the edge of the arena
+pub struct EnemyBounced {
+    pub position: Vec3,
+}
+
+//sent when an enemy passes through the graze ring without touching the player
+pub struct Grazed {
+    pub position: Vec3,
+}
+
+//sent when the player picks up a power-up
+pub struct PowerUpCollected {
+    pub kind: PowerUpKind,
+}
+
+//sent when an enemy or projectile kills the player
+pub struct PlayerHit {
+    pub position: Vec3,
+}
@@ -0,0 +1,310 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::arena::*;
+use crate::resources::RunSeed;
+
+pub const GENERATED_ARENA_SIZE: Vec2 = Vec2::new(1280.0, 720.0);
+pub const GENERATOR_CELL_SIZE: f32 = 80.0; //obstacles snap to cells this big, wider than the player
+pub const MIN_OBSTACLES: usize = 8;
+pub const MAX_OBSTACLES: usize = 14;
+pub const OBSTACLE_ATTEMPTS: usize = 200; //placements tried before settling for fewer obstacles
+pub const PLAYER_START_CLEARANCE: f32 = 160.0; //no obstacle closer than this to the player start
+pub const SPAWN_REGION_CLEARANCE: f32 = 80.0; //no obstacle closer than this to a spawn region
+pub const ENEMY_REGION_CELLS: f32 = 2.0; //enemies spawn in corner squares this many cells wide
+pub const EXPORT_ARENA_KEY: KeyCode = KeyCode::F2;
+
+//grid of cells the generator places obstacles on, true means blocked
+struct Grid {
+    columns: usize,
+    rows: usize,
+    blocked: Vec<bool>,
+}
+
+impl Grid {
+    fn new(columns: usize, rows: usize) -> Grid {
+        Grid {
+            columns,
+            rows,
+            blocked: vec![false; columns * rows],
+        }
+    }
+
+    fn index(&self, column: usize, row: usize) -> usize {
+        row * self.columns + column
+    }
+
+    fn cell_center(&self, column: usize, row: usize) -> Vec2 {
+        (Vec2::new(column as f32, row as f32) + 0.5) * GENERATOR_CELL_SIZE
+    }
+
+    //true when every open cell can be reached from the start cell
+    fn is_connected(&self, start: (usize, usize)) -> bool {
+        let mut visited = vec![false; self.blocked.len()];
+        let mut queue = VecDeque::from([start]);
+        visited[self.index(start.0, start.1)] = true;
+        let mut reached = 1;
+
+        while let Some((column, row)) = queue.pop_front() {
+            let neighbours = [
+                (column.wrapping_sub(1), row),
+                (column + 1, row),
+                (column, row.wrapping_sub(1)),
+                (column, row + 1),
+            ];
+            for (next_column, next_row) in neighbours {
+                if next_column >= self.columns || next_row >= self.rows {
+                    continue;
+                }
+                let index = self.index(next_column, next_row);
+                if !visited[index] && !self.blocked[index] {
+                    visited[index] = true;
+                    reached += 1;
+                    queue.push_back((next_column, next_row));
+                }
+            }
+        }
+
+        reached == self.blocked.iter().filter(|blocked| !**blocked).count()
+    }
+}
+
+/*
+ function for generating an arena from a seed, the same seed always gives the same arena.
+ obstacles are placed on a grid of cells, and one is only kept if every open cell can
+ still be reached from the player start and it stays clear of the player start and spawn regions
+*/
+pub fn generate_arena(seed: u64) -> Arena {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let size = GENERATED_ARENA_SIZE;
+    let player_start = size / 2.0;
+
+    //enemies come in from the four corners
+    let corner = Vec2::splat(ENEMY_REGION_CELLS * GENERATOR_CELL_SIZE);
+    let enemy_spawn_regions = vec![
+        SpawnRegion {
+            min: Vec2::ZERO,
+            max: corner,
+        },
+        SpawnRegion {
+            min: Vec2::new(size.x - corner.x, 0.0),
+            max: Vec2::new(size.x, corner.y),
+        },
+        SpawnRegion {
+            min: Vec2::new(0.0, size.y - corner.y),
+            max: Vec2::new(corner.x, size.y),
+        },
+        SpawnRegion {
+            min: size - corner,
+            max: size,
+        },
+    ];
+
+    let mut grid = Grid::new(
+        (size.x / GENERATOR_CELL_SIZE) as usize,
+        (size.y / GENERATOR_CELL_SIZE) as usize,
+    );
+    let start_cell = (
+        (player_start.x / GENERATOR_CELL_SIZE) as usize,
+        (player_start.y / GENERATOR_CELL_SIZE) as usize,
+    );
+
+    //cells that must stay open, near the player start or a spawn region
+    let mut reserved = vec![false; grid.blocked.len()];
+    for row in 0..grid.rows {
+        for column in 0..grid.columns {
+            let center = grid.cell_center(column, row);
+            let near_start =
+                center.distance(player_start) < PLAYER_START_CLEARANCE + GENERATOR_CELL_SIZE / 2.0;
+            let near_region = enemy_spawn_regions.iter().any(|region| {
+                center.clamp(region.min, region.max).distance(center)
+                    < SPAWN_REGION_CLEARANCE + GENERATOR_CELL_SIZE / 2.0
+            });
+            reserved[grid.index(column, row)] = near_start || near_region;
+        }
+    }
+
+    let target = rng.gen_range(MIN_OBSTACLES..=MAX_OBSTACLES);
+    let mut walls = Vec::new();
+    for _ in 0..OBSTACLE_ATTEMPTS {
+        if walls.len() >= target {
+            break;
+        }
+
+        //a single round bumper, or a bar up to three cells long
+        let (width, height) = match rng.gen_range(0..3) {
+            0 => (1, 1),
+            1 => (rng.gen_range(2..=3), 1),
+            _ => (1, rng.gen_range(2..=3)),
+        };
+        if width > grid.columns || height > grid.rows {
+            continue;
+        }
+        let column = rng.gen_range(0..=grid.columns - width);
+        let row = rng.gen_range(0..=grid.rows - height);
+
+        let cells: Vec<usize> = (row..row + height)
+            .flat_map(|r| (column..column + width).map(move |c| (c, r)))
+            .map(|(c, r)| grid.index(c, r))
+            .collect();
+        if cells
+            .iter()
+            .any(|&index| reserved[index] || grid.blocked[index])
+        {
+            continue;
+        }
+
+        for &index in cells.iter() {
+            grid.blocked[index] = true;
+        }
+        if !grid.is_connected(start_cell) {
+            for &index in cells.iter() {
+                grid.blocked[index] = false;
+            }
+            continue;
+        }
+
+        let min = Vec2::new(column as f32, row as f32) * GENERATOR_CELL_SIZE;
+        let max = Vec2::new((column + width) as f32, (row + height) as f32) * GENERATOR_CELL_SIZE;
+        let center = (min + max) / 2.0;
+        walls.push(if width == 1 && height == 1 {
+            WallLayout::Circle {
+                center,
+                radius: GENERATOR_CELL_SIZE * rng.gen_range(0.3..0.5),
+            }
+        } else {
+            //bars are a little thinner than their cells so they don't read as a solid block
+            let thickness = GENERATOR_CELL_SIZE * rng.gen_range(0.4..0.8);
+            WallLayout::Rect {
+                center,
+                size: if width > 1 {
+                    Vec2::new(max.x - min.x, thickness)
+                } else {
+                    Vec2::new(thickness, max.y - min.y)
+                },
+            }
+        });
+    }
+
+    Arena {
+        name: format!("Generated {}", seed),
+        size,
+        player_start,
+        boundary: Boundary::default(),
+        background: Background::default(),
+        walls,
+        enemy_spawn_regions,
+        star_spawn_regions: Vec::new(), //stars can appear anywhere open
+    }
+}
+
+//path a generated arena is exported to
+pub fn generated_arena_path(seed: u64) -> String {
+    format!("assets/arenas/generated_{}.ron", seed)
+}
+
+/*
+ system for exporting a generated arena, so a good layout can be kept, with parameter
+ - keyboard_input to know when the export key is pressed
+ - arena to write
+ - run_seed to name the export, arenas loaded from a file are never exported so they can't be overwritten
+*/
+pub fn export_arena(
+    keyboard_input: Res<Input<KeyCode>>,
+    arena: Res<Arena>,
+    run_seed: Res<RunSeed>,
+) {
+    if keyboard_input.just_pressed(EXPORT_ARENA_KEY) {
+        let Some(seed) = run_seed.value else {
+            println!("Only generated arenas can be exported");
+            return;
+        };
+
+        let path = generated_arena_path(seed);
+        match arena.save(&path) {
+            Ok(()) => println!("Arena exported to {}", path),
+            Err(error) => eprintln!("Failed to export arena: {}", error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEEDS: std::ops::Range<u64> = 0..64;
+
+    //grid with every cell whose center is covered by a wall blocked
+    fn blocked_grid(arena: &Arena) -> Grid {
+        let mut grid = Grid::new(
+            (arena.size.x / GENERATOR_CELL_SIZE) as usize,
+            (arena.size.y / GENERATOR_CELL_SIZE) as usize,
+        );
+        for row in 0..grid.rows {
+            for column in 0..grid.columns {
+                let center = grid.cell_center(column, row);
+                let index = grid.index(column, row);
+                grid.blocked[index] = arena.walls.iter().any(|wall| {
+                    wall.shape()
+                        .collide_circle(wall.center(), center, 0.0)
+                        .is_some()
+                });
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn same_seed_gives_same_arena() {
+        for seed in SEEDS {
+            assert_eq!(generate_arena(seed), generate_arena(seed));
+        }
+    }
+
+    #[test]
+    fn open_cells_stay_connected() {
+        for seed in SEEDS {
+            let arena = generate_arena(seed);
+            assert!(!arena.walls.is_empty(), "seed {} placed no obstacles", seed);
+            let grid = blocked_grid(&arena);
+            let start_cell = (
+                (arena.player_start.x / GENERATOR_CELL_SIZE) as usize,
+                (arena.player_start.y / GENERATOR_CELL_SIZE) as usize,
+            );
+            assert!(grid.is_connected(start_cell), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn start_and_spawn_regions_stay_open() {
+        for seed in SEEDS {
+            let arena = generate_arena(seed);
+            let grid = blocked_grid(&arena);
+            for row in 0..grid.rows {
+                for column in 0..grid.columns {
+                    if !grid.blocked[grid.index(column, row)] {
+                        continue;
+                    }
+                    let center = grid.cell_center(column, row);
+                    assert!(
+                        center.distance(arena.player_start)
+                            >= PLAYER_START_CLEARANCE + GENERATOR_CELL_SIZE / 2.0,
+                        "seed {} blocks the player start",
+                        seed
+                    );
+                    for region in arena.enemy_spawn_regions.iter() {
+                        assert!(
+                            center.clamp(region.min, region.max).distance(center)
+                                >= SPAWN_REGION_CLEARANCE + GENERATOR_CELL_SIZE / 2.0,
+                            "seed {} blocks a spawn region",
+                            seed
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
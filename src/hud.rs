@@ -0,0 +1,793 @@
+use bevy::diagnostic::{Diagnostics, EntityCountDiagnosticsPlugin};
+use bevy::prelude::*;
+
+use crate::arena::Arena;
+use crate::components::*;
+use crate::events::*;
+use crate::resources::*;
+
+pub const HUD_FONT: &str = "fonts/DejaVuSans.ttf";
+pub const HUD_FONT_SIZE: f32 = 24.0;
+
+pub const INDICATOR_RANGE: f32 = 1200.0; //off-screen things further from the player than this get no indicator
+pub const MAX_INDICATORS: usize = 12; //only the nearest ones get an indicator
+pub const INDICATOR_SIZE: Vec2 = Vec2::new(24.0, 8.0);
+pub const INDICATOR_MARGIN: f32 = 16.0; //distance from the screen edge
+pub const INDICATOR_Z: f32 = 50.0; //above every sprite in the world
+pub const MINIMAP_KEY: KeyCode = KeyCode::M;
+pub const MINIMAP_WIDTH: f32 = 200.0; //the height follows the arena's aspect ratio
+pub const MINIMAP_MARGIN: f32 = 10.0;
+pub const MINIMAP_BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.5);
+pub const MINIMAP_VIEWPORT_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.15);
+pub const MINIMAP_PLAYER_SIZE: f32 = 6.0;
+pub const MINIMAP_ENEMY_SIZE: f32 = 4.0;
+pub const MINIMAP_STAR_SIZE: f32 = 3.0;
+
+//colors the HUD uses to tell the player, enemies and stars apart
+pub struct Palette {
+    pub player: Color,
+    pub enemy: Color,
+    pub star: Color,
+    pub tint_sprites: bool, //draw the player and enemies as tinted grey balls instead of their colored textures
+}
+
+pub const DEFAULT_PALETTE: Palette = Palette {
+    player: Color::rgb(0.3, 0.6, 1.0),
+    enemy: Color::rgb(1.0, 0.3, 0.3),
+    star: Color::rgb(1.0, 0.9, 0.3),
+    tint_sprites: false,
+};
+
+//blue, orange and white stay apart for the common kinds of colour blindness
+pub const COLORBLIND_PALETTE: Palette = Palette {
+    player: Color::rgb(0.35, 0.7, 0.9),
+    enemy: Color::rgb(0.9, 0.6, 0.0),
+    star: Color::rgb(0.95, 0.95, 0.95),
+    tint_sprites: true,
+};
+
+pub const TINTED_SPRITE_TEXTURE: &str = "sprites/ball_grey_large.png";
+
+impl Palette {
+    //texture and tint of the player sprite
+    pub fn player_sprite(&self) -> (&'static str, Color) {
+        if self.tint_sprites {
+            (TINTED_SPRITE_TEXTURE, self.player)
+        } else {
+            (PLAYER_TEXTURE, Color::WHITE)
+        }
+    }
+
+    //texture and tint of an enemy sprite, the colourblind tints keep red and green kinds apart
+    pub fn enemy_sprite(&self, kind: EnemyKind) -> (&'static str, Color) {
+        if !self.tint_sprites {
+            return (kind.texture(), Color::WHITE);
+        }
+        let color = match kind {
+            EnemyKind::Bouncer | EnemyKind::Boss => Color::rgb(0.84, 0.37, 0.0),
+            EnemyKind::Chaser => Color::rgb(0.8, 0.47, 0.65),
+            EnemyKind::Wanderer => Color::rgb(0.0, 0.62, 0.45),
+            EnemyKind::Orbiter => Color::rgb(0.94, 0.89, 0.26),
+            EnemyKind::Swarm => self.enemy,
+        };
+        (TINTED_SPRITE_TEXTURE, color)
+    }
+
+    //tint of a star sprite
+    pub fn star_color(&self, kind: StarKind) -> Color {
+        if !self.tint_sprites {
+            return kind.color();
+        }
+        match kind {
+            StarKind::Common => self.star,
+            StarKind::Golden => Color::rgb(0.94, 0.89, 0.26),
+            StarKind::Drifting => Color::rgb(0.0, 0.45, 0.7),
+            StarKind::Fading => Color::rgb(0.8, 0.47, 0.65),
+        }
+    }
+}
+
+pub fn palette(settings: &Settings) -> &'static Palette {
+    if settings.colorblind_palette {
+        &COLORBLIND_PALETTE
+    } else {
+        &DEFAULT_PALETTE
+    }
+}
+
+//sprite apply_sprite_palette redraws, with the component telling it what to draw
+type PaletteSprite<'a, T> = (Ref<'a, T>, &'a mut Sprite, &'a mut Handle<Image>);
+
+//stars, kept apart from the player and enemy sprites
+type StarSpriteFilter = (Without<Player>, Without<Enemy>);
+
+/*
+ system for drawing the player, enemies and stars in the palette's colours, with parameter
+ - settings for the palette, every sprite is redrawn when it changes
+ - player_query, enemy_query and star_query for the sprites, new and reused ones are redrawn as they appear
+ - asset_server to swap the textures
+*/
+pub fn apply_sprite_palette(
+    settings: Res<Settings>,
+    mut player_query: Query<PaletteSprite<Player>>,
+    mut enemy_query: Query<PaletteSprite<Enemy>, Without<Player>>,
+    mut star_query: Query<(Ref<Star>, &mut Sprite), StarSpriteFilter>,
+    asset_server: Res<AssetServer>,
+) {
+    let palette = palette(&settings);
+    let set_sprite =
+        |sprite: &mut Sprite, texture: &mut Handle<Image>, (path, color): (&'static str, Color)| {
+            let new_texture = asset_server.load(path);
+            if *texture != new_texture {
+                *texture = new_texture;
+            }
+            sprite.color = color.with_a(sprite.color.a());
+        };
+
+    for (player, mut sprite, mut texture) in player_query.iter_mut() {
+        if settings.is_changed() || player.is_added() {
+            set_sprite(&mut sprite, &mut texture, palette.player_sprite());
+        }
+    }
+    for (enemy, mut sprite, mut texture) in enemy_query.iter_mut() {
+        if settings.is_changed() || enemy.is_added() {
+            set_sprite(&mut sprite, &mut texture, palette.enemy_sprite(enemy.kind));
+        }
+    }
+    for (star, mut sprite) in star_query.iter_mut() {
+        if settings.is_changed() || star.is_added() {
+            sprite.color = palette.star_color(star.kind).with_a(sprite.color.a());
+        }
+    }
+}
+
+/*
+ system untuk spawn HUD dengan parameter
+ - commands untuk memberikan command,
+ - asset_server untuk menggunakan font
+*/
+pub fn spawn_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load(HUD_FONT),
+                font_size: HUD_FONT_SIZE,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(10.0),
+                right: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+        EffectsText {},
+    ));
+}
+
+/*
+ system for showing the remaining time of every active effect, with parameter
+ - player_query to read the player's active effects
+ - text_query to write into the HUD text
+*/
+pub fn update_effects_hud(
+    player_query: Query<&ActiveEffects, (With<Player>, Changed<ActiveEffects>)>,
+    mut text_query: Query<&mut Text, With<EffectsText>>,
+) {
+    if let Ok(active_effects) = player_query.get_single() {
+        if let Ok(mut text) = text_query.get_single_mut() {
+            let lines: Vec<String> = active_effects
+                .effects
+                .iter()
+                .map(|effect| {
+                    if effect.kind.stacks() {
+                        format!(
+                            "{}{} {:.1}s",
+                            effect.kind.label(),
+                            effect.stacks + 1,
+                            effect.timer.remaining_secs()
+                        )
+                    } else {
+                        format!(
+                            "{} {:.1}s",
+                            effect.kind.label(),
+                            effect.timer.remaining_secs()
+                        )
+                    }
+                })
+                .collect();
+            text.sections[0].value = lines.join("\n");
+        }
+    }
+}
+
+//system for spawning the metrics text in the bottom-left corner
+pub fn spawn_metrics_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load(HUD_FONT),
+                font_size: HUD_FONT_SIZE * 0.75,
+                color: Color::GRAY,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(10.0),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+        MetricsText {},
+    ));
+}
+
+/*
+ system for showing entity counts, so the steady state after capping and pooling can be checked, with parameter
+ - diagnostics to read the total entity count
+ - star_query, enemy_query and projectile_query to count the live stars, enemies and projectiles
+ - entity_limits and entity_pool for the caps and the number of pooled sprites
+*/
+pub fn update_metrics_hud(
+    diagnostics: Res<Diagnostics>,
+    star_query: Query<(), With<Star>>,
+    enemy_query: Query<&Enemy>,
+    projectile_query: Query<(), With<Projectile>>,
+    entity_limits: Res<EntityLimits>,
+    entity_pool: Res<EntityPool>,
+    mut text_query: Query<&mut Text, With<MetricsText>>,
+) {
+    if let Ok(mut text) = text_query.get_single_mut() {
+        let entity_count = diagnostics
+            .get(EntityCountDiagnosticsPlugin::ENTITY_COUNT)
+            .and_then(|diagnostic| diagnostic.value())
+            .unwrap_or(0.0);
+
+        let swarm_count = enemy_query
+            .iter()
+            .filter(|enemy| enemy.kind == EnemyKind::Swarm)
+            .count();
+
+        text.sections[0].value = format!(
+            "Entities: {} | Stars: {}/{} | Enemies: {}/{} | Swarm: {}/{} | Projectiles: {}/{} | Pooled: {}",
+            entity_count,
+            star_query.iter().count(),
+            entity_limits.max_stars,
+            enemy_query.iter().count() - swarm_count,
+            entity_limits.max_enemies,
+            swarm_count,
+            entity_limits.max_swarm_enemies,
+            projectile_query.iter().count(),
+            entity_limits.max_projectiles,
+            entity_pool.pooled_count(),
+        );
+    }
+}
+
+//system for spawning the score text in the top-left corner
+pub fn spawn_score_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "Score: 0",
+            TextStyle {
+                font: asset_server.load(HUD_FONT),
+                font_size: HUD_FONT_SIZE,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(10.0),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+        ScoreText {},
+    ));
+}
+
+/*
+ system for showing the score and combo multiplier, with parameter
+ - score and combo to read from
+ - text_query to write into the HUD text
+*/
+pub fn update_score_hud(
+    score: Res<Score>,
+    combo: Res<Combo>,
+    mut text_query: Query<&mut Text, With<ScoreText>>,
+) {
+    if !score.is_changed() && !combo.is_changed() {
+        return;
+    }
+
+    if let Ok(mut text) = text_query.get_single_mut() {
+        text.sections[0].value = if combo.count > 1 {
+            format!("Score: {}  Combo x{:.1}", score.value, combo.multiplier())
+        } else {
+            format!("Score: {}", score.value)
+        };
+    }
+}
+
+/*
+ system for showing the game-over screen with the score breakdown, with parameter
+ - game_over_event_reader to know when the game is over
+ - score to read the breakdown from
+ - game_over_screen_query so the screen is only spawned once
+*/
+pub fn spawn_game_over_screen(
+    mut commands: Commands,
+    mut game_over_event_reader: EventReader<GameOver>,
+    asset_server: Res<AssetServer>,
+    score: Res<Score>,
+    game_over_screen_query: Query<(), With<GameOverScreen>>,
+) {
+    if game_over_event_reader.iter().last().is_none() || !game_over_screen_query.is_empty() {
+        return;
+    }
+
+    let font = asset_server.load(HUD_FONT);
+    let text_style = TextStyle {
+        font,
+        font_size: HUD_FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                ..default()
+            },
+            GameOverScreen {},
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Game Over",
+                TextStyle {
+                    font_size: HUD_FONT_SIZE * 2.0,
+                    ..text_style.clone()
+                },
+            ));
+            for line in [
+                format!("Stars: {}", score.base_points),
+                format!("Combo bonus: {}", score.combo_bonus),
+                format!("Other bonuses: {}", score.bonus_points),
+                format!("Total: {}", score.value),
+            ] {
+                parent.spawn(TextBundle::from_section(line, text_style.clone()));
+            }
+        });
+}
+
+//system for spawning the boss health bar when a boss appears
+pub fn spawn_boss_health_bar(
+    mut commands: Commands,
+    boss_query: Query<(), Added<Boss>>,
+    health_bar_query: Query<(), With<BossHealthBar>>,
+) {
+    if boss_query.is_empty() || !health_bar_query.is_empty() {
+        return;
+    }
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        top: Val::Px(10.0),
+                        left: Val::Percent(30.0),
+                        ..default()
+                    },
+                    size: Size::new(Val::Percent(40.0), Val::Px(16.0)),
+                    ..default()
+                },
+                background_color: Color::rgb(0.2, 0.2, 0.2).into(),
+                ..default()
+            },
+            BossHealthBar {},
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                        ..default()
+                    },
+                    background_color: Color::CRIMSON.into(),
+                    ..default()
+                },
+                BossHealthFill {},
+            ));
+        });
+}
+
+/*
+ system for keeping the boss health bar in sync, with parameter
+ - boss_query to read the boss's health
+ - fill_query to resize the bar
+ - health_bar_query to remove the bar once the boss is gone
+*/
+pub fn update_boss_health_bar(
+    mut commands: Commands,
+    boss_query: Query<&Boss>,
+    mut fill_query: Query<&mut Style, With<BossHealthFill>>,
+    health_bar_query: Query<Entity, With<BossHealthBar>>,
+) {
+    match boss_query.get_single() {
+        Ok(boss) => {
+            for mut style in fill_query.iter_mut() {
+                let percent = (boss.health / boss.max_health).clamp(0.0, 1.0) * 100.0;
+                style.size.width = Val::Percent(percent);
+            }
+        }
+        Err(_) => {
+            for health_bar in health_bar_query.iter() {
+                commands.entity(health_bar).despawn_recursive();
+            }
+        }
+    }
+}
+
+//system for spawning the dash cooldown text under the score
+pub fn spawn_dash_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load(HUD_FONT),
+                font_size: HUD_FONT_SIZE * 0.75,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(10.0 + HUD_FONT_SIZE * 1.5),
+                left: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+        DashText {},
+    ));
+}
+
+/*
+ system for showing the dash cooldown, with parameter
+ - player_query to read the player's dash
+ - text_query to write into the HUD text
+*/
+pub fn update_dash_hud(
+    player_query: Query<&Dash, (With<Player>, Changed<Dash>)>,
+    mut text_query: Query<&mut Text, With<DashText>>,
+) {
+    if let Ok(dash) = player_query.get_single() {
+        if let Ok(mut text) = text_query.get_single_mut() {
+            text.sections[0].value = if dash.is_ready() {
+                "Dash: ready".to_string()
+            } else {
+                format!("Dash: {:.1}s", dash.cooldown_left)
+            };
+        }
+    }
+}
+
+//minimap viewport rectangle, kept apart from the minimap and its markers
+type MinimapViewportFilter = (
+    With<MinimapViewport>,
+    Without<MinimapMarker>,
+    Without<Minimap>,
+);
+
+//minimap markers, kept apart from the viewport rectangle
+type MinimapMarkerFilter = (
+    With<MinimapMarker>,
+    Without<MinimapViewport>,
+    Without<Minimap>,
+);
+
+//indicators, kept apart from everything else update_offscreen_indicators reads a transform from
+type IndicatorFilter = (
+    With<OffscreenIndicator>,
+    Without<GameCamera>,
+    Without<Player>,
+    Without<Enemy>,
+    Without<Star>,
+);
+
+/*
+ system for pointing at nearby enemies and stars that are off screen, with parameter
+ - camera_query to know what part of the world is on screen
+ - player_query to measure how near things are
+ - enemy_query and star_query for the things to point at
+ - indicator_query to reuse the indicators from the last frame
+ - arena to measure across the seam of a wrapping arena
+ - settings for the colour palette
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn update_offscreen_indicators(
+    mut commands: Commands,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<GameCamera>>,
+    player_query: Query<&Transform, With<Player>>,
+    enemy_query: Query<&Transform, With<Enemy>>,
+    star_query: Query<&Transform, With<Star>>,
+    mut indicator_query: Query<(&mut Transform, &mut Sprite, &mut Visibility), IndicatorFilter>,
+    arena: Res<Arena>,
+    settings: Res<Settings>,
+) {
+    let Ok((camera_transform, projection)) = camera_query.get_single() else {
+        return;
+    };
+    let palette = palette(&settings);
+    let camera = camera_transform.translation.truncate();
+    let half_view = projection.area.size() / 2.0;
+    let player = player_query
+        .get_single()
+        .ok()
+        .map(|transform| transform.translation);
+
+    //off-screen things near the player, nearest first
+    let mut targets: Vec<(f32, Vec2, Color)> = Vec::new();
+    if let Some(player) = player {
+        let things = enemy_query
+            .iter()
+            .map(|transform| (transform.translation, palette.enemy))
+            .chain(
+                star_query
+                    .iter()
+                    .map(|transform| (transform.translation, palette.star)),
+            );
+        for (translation, color) in things {
+            let distance = arena.distance(player, translation);
+            let offset = arena.offset(camera, translation.truncate());
+            let on_screen = offset.x.abs() < half_view.x && offset.y.abs() < half_view.y;
+            if distance < INDICATOR_RANGE && !on_screen {
+                targets.push((distance, offset, color));
+            }
+        }
+    }
+    targets.sort_by(|a, b| a.0.total_cmp(&b.0));
+    targets.truncate(MAX_INDICATORS);
+
+    let mut indicators = indicator_query.iter_mut();
+    for (distance, offset, color) in targets {
+        //on the edge of the screen, along the line from the centre to the target
+        let edge = half_view - INDICATOR_MARGIN;
+        let scale = (edge.x / offset.x.abs()).min(edge.y / offset.y.abs());
+        let transform = Transform::from_translation((camera + offset * scale).extend(INDICATOR_Z))
+            .with_rotation(Quat::from_rotation_z(offset.y.atan2(offset.x)));
+        //fade out with distance so the nearest ones stand out
+        let mut color = color;
+        color.set_a((1.0 - distance / INDICATOR_RANGE).max(0.3));
+
+        match indicators.next() {
+            Some((mut indicator_transform, mut sprite, mut visibility)) => {
+                *indicator_transform = transform;
+                sprite.color = color;
+                *visibility = Visibility::Inherited;
+            }
+            None => {
+                commands.spawn((
+                    SpriteBundle {
+                        sprite: Sprite {
+                            color,
+                            custom_size: Some(INDICATOR_SIZE),
+                            ..default()
+                        },
+                        transform,
+                        ..default()
+                    },
+                    OffscreenIndicator {},
+                ));
+            }
+        }
+    }
+
+    //hide the indicators that weren't needed this frame
+    for (_, _, mut visibility) in indicators {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+//system for spawning the minimap in the bottom-right corner, sized to the arena's aspect ratio
+pub fn spawn_minimap(mut commands: Commands, arena: Res<Arena>, settings: Res<Settings>) {
+    let size = minimap_size(&arena);
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        bottom: Val::Px(MINIMAP_MARGIN),
+                        right: Val::Px(MINIMAP_MARGIN),
+                        ..default()
+                    },
+                    size: Size::new(Val::Px(size.x), Val::Px(size.y)),
+                    ..default()
+                },
+                background_color: MINIMAP_BACKGROUND.into(),
+                visibility: if settings.show_minimap {
+                    Visibility::Inherited
+                } else {
+                    Visibility::Hidden
+                },
+                ..default()
+            },
+            Minimap {},
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        ..default()
+                    },
+                    background_color: MINIMAP_VIEWPORT_COLOR.into(),
+                    ..default()
+                },
+                MinimapViewport {},
+            ));
+        });
+}
+
+//system for showing and hiding the minimap with the minimap key
+pub fn toggle_minimap(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+    if keyboard_input.just_pressed(MINIMAP_KEY) {
+        settings.show_minimap = !settings.show_minimap;
+    }
+}
+
+//minimap size in pixels, MINIMAP_WIDTH wide with the arena's aspect
+fn minimap_size(arena: &Arena) -> Vec2 {
+    Vec2::new(
+        MINIMAP_WIDTH,
+        MINIMAP_WIDTH * arena.height() / arena.width(),
+    )
+}
+
+//style for a box on the minimap, from its world space centre and size
+fn minimap_rect(arena: &Arena, map_size: Vec2, center: Vec2, world_size: Vec2) -> Style {
+    let scale = map_size / arena.size;
+    let size = world_size * scale;
+    let min = center * scale - size / 2.0;
+    Style {
+        position_type: PositionType::Absolute,
+        position: UiRect {
+            left: Val::Px(min.x),
+            bottom: Val::Px(min.y),
+            ..default()
+        },
+        size: Size::new(Val::Px(size.x), Val::Px(size.y)),
+        ..default()
+    }
+}
+
+/*
+ system for drawing the player, enemies, stars and camera view on the minimap, with parameter
+ - settings to know whether the minimap is shown and for the colour palette
+ - minimap_query for the minimap and its size, resized whenever the arena changes
+ - viewport_query and camera_query for the camera view rectangle
+ - player_query, enemy_query and star_query for the markers
+ - marker_query to reuse the markers from the last frame
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn update_minimap(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    arena: Res<Arena>,
+    mut minimap_query: Query<(Entity, &Node, &mut Style, &mut Visibility), With<Minimap>>,
+    mut viewport_query: Query<&mut Style, MinimapViewportFilter>,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<GameCamera>>,
+    player_query: Query<&Transform, With<Player>>,
+    enemy_query: Query<&Transform, With<Enemy>>,
+    star_query: Query<&Transform, With<Star>>,
+    mut marker_query: Query<
+        (&mut Style, &mut BackgroundColor, &mut Visibility),
+        MinimapMarkerFilter,
+    >,
+) {
+    let Ok((minimap_entity, node, mut minimap_style, mut minimap_visibility)) =
+        minimap_query.get_single_mut()
+    else {
+        return;
+    };
+    //keep the arena's aspect when an arena is loaded or edited
+    if arena.is_changed() {
+        let size = minimap_size(&arena);
+        minimap_style.size = Size::new(Val::Px(size.x), Val::Px(size.y));
+    }
+    if !settings.show_minimap {
+        *minimap_visibility = Visibility::Hidden;
+        return;
+    }
+    *minimap_visibility = Visibility::Inherited;
+
+    //the node has no size until the UI layout ran, nothing can be placed on it before that
+    let map_size = node.size();
+    if map_size.x <= 0.0 || map_size.y <= 0.0 {
+        return;
+    }
+    let palette = palette(&settings);
+
+    if let (Ok(mut style), Ok((camera_transform, projection))) =
+        (viewport_query.get_single_mut(), camera_query.get_single())
+    {
+        *style = minimap_rect(
+            &arena,
+            map_size,
+            camera_transform.translation.truncate(),
+            projection.area.size().min(arena.size),
+        );
+    }
+
+    //markers are drawn in this order, so the player ends up on top
+    let markers = star_query
+        .iter()
+        .map(|transform| (transform, palette.star, MINIMAP_STAR_SIZE))
+        .chain(
+            enemy_query
+                .iter()
+                .map(|transform| (transform, palette.enemy, MINIMAP_ENEMY_SIZE)),
+        )
+        .chain(
+            player_query
+                .iter()
+                .map(|transform| (transform, palette.player, MINIMAP_PLAYER_SIZE)),
+        );
+
+    let mut marker_entities = marker_query.iter_mut();
+    for (transform, color, size) in markers {
+        //marker size is in minimap pixels, turn it back into world units for minimap_rect
+        let world_size = Vec2::splat(size) * arena.size / map_size;
+        let style = minimap_rect(
+            &arena,
+            map_size,
+            transform.translation.truncate(),
+            world_size,
+        );
+
+        match marker_entities.next() {
+            Some((mut marker_style, mut background_color, mut visibility)) => {
+                *marker_style = style;
+                *background_color = color.into();
+                *visibility = Visibility::Inherited;
+            }
+            None => {
+                commands.entity(minimap_entity).with_children(|parent| {
+                    parent.spawn((
+                        NodeBundle {
+                            style,
+                            background_color: color.into(),
+                            ..default()
+                        },
+                        MinimapMarker {},
+                    ));
+                });
+            }
+        }
+    }
+
+    //hide the markers that weren't needed this frame
+    for (_, _, mut visibility) in marker_entities {
+        *visibility = Visibility::Hidden;
+    }
+}
@@ -1,41 +1,273 @@
+use bevy::diagnostic::EntityCountDiagnosticsPlugin;
 use bevy::prelude::*;
+use bevy::window::WindowPlugin;
 
+mod arena;
+mod audio;
 pub mod components;
+mod display;
+mod editor;
 pub mod events;
+mod generator;
+mod hud;
+mod menu;
+mod music;
 pub mod resources;
+mod save;
+mod states;
 mod systems;
 
+use arena::*;
+use audio::*;
+use display::*;
+use editor::*;
 use events::*;
+use generator::*;
+use hud::*;
+use menu::*;
+use music::*;
 use resources::*;
+use save::*;
+use states::*;
 use systems::*;
 
 fn main() {
+    let (arena, arena_file, run_seed) = arena_from_args();
+
     App::new()
-        .add_plugins(DefaultPlugins)
+        .add_plugins(DefaultPlugins.set(WindowPlugin {
+            close_when_requested: false, //handle_close_requested saves first, and asks if a run is going
+            ..default()
+        }))
+        .add_plugin(EntityCountDiagnosticsPlugin)
+        .add_plugin(GameAudioPlugin)
+        .add_plugin(MusicPlugin)
+        .add_state::<AppState>()
+        .insert_resource(ClearColor(LETTERBOX_COLOR))
+        .insert_resource(arena)
+        .insert_resource(arena_file)
+        .insert_resource(run_seed)
         .init_resource::<Score>() // add score resource with default value and keep tracking score not delete prev value
         .init_resource::<StarSpawnTimer>()
         .init_resource::<EnemySpawnTimer>()
-        .init_resource::<HighScores>()
+        .insert_resource(load_or_default::<HighScores>(HIGH_SCORES_PATH))
+        .init_resource::<PowerUpSpawnTimer>()
+        .init_resource::<PowerUpSpawnTable>()
+        .init_resource::<StarSpawnTable>()
+        .init_resource::<EnemySpawnTable>()
+        .init_resource::<SwarmSpawnTimer>()
+        .init_resource::<SwarmGrid>()
+        .init_resource::<EntityLimits>()
+        .init_resource::<EntityPool>()
+        .init_resource::<Combo>()
+        .init_resource::<BossSpawnTimer>()
+        .init_resource::<MovementSettings>()
+        .init_resource::<EditorState>()
+        .insert_resource(load_or_default::<Settings>(SETTINGS_PATH))
+        .init_resource::<MenuCursor>()
+        .init_resource::<ResumeCountdown>()
+        .init_resource::<Rebinding>()
         .add_event::<GameOver>()
+        .add_event::<StarCollected>()
+        .add_event::<BossDefeated>()
+        .add_event::<Quit>()
+        .add_event::<EnemyBounced>()
+        .add_event::<Grazed>()
+        .add_event::<PowerUpCollected>()
+        .add_event::<PlayerHit>()
         .add_startup_system(spawn_camera)
+        .add_startup_system(spawn_arena.in_base_set(StartupSet::PreStartup))
         .add_startup_system(spawn_player)
         .add_startup_system(spawn_enemies)
         .add_startup_system(spawn_stars)
-        .add_system(player_movement)
-        .add_system(enemy_movement)
-        .add_system(enemy_hit_player)
-        .add_system(update_enemy_direction)
-        .add_system(confine_player_movement)
-        .add_system(cofine_enemy_movement)
-        .add_system(player_hit_star)
+        .add_startup_system(spawn_hud)
+        .add_startup_system(spawn_metrics_hud)
+        .add_startup_system(spawn_score_hud)
+        .add_startup_system(spawn_dash_hud)
+        .add_startup_system(spawn_minimap)
+        //gameplay only runs while playing, the editor freezes it
+        .add_systems(
+            (
+                player_movement,
+                update_swarm_grid.before(enemy_movement),
+                enemy_movement,
+                enemy_hit_player,
+                update_floating_text,
+                update_enemy_direction,
+                confine_player_movement,
+                cofine_enemy_movement,
+                player_hit_wall.after(confine_player_movement),
+                enemy_hit_wall.after(cofine_enemy_movement),
+                spawn_dash_afterimages,
+                fade_afterimages,
+            )
+                .in_set(OnUpdate(AppState::Game)),
+        )
+        .add_systems(
+            (
+                player_hit_star,
+                star_drift,
+                tick_star_lifetime,
+                tick_combo,
+                tick_star_spawn_timer,
+                spawn_star_overtime,
+                tick_enemy_spawn_timer,
+                spawn_enemies_overtime,
+                tick_swarm_spawn_timer,
+                spawn_swarm_overtime,
+                tick_power_up_spawn_timer,
+                spawn_power_up_overtime,
+                player_hit_power_up,
+                tick_active_effects,
+                star_magnet,
+            )
+                .in_set(OnUpdate(AppState::Game)),
+        )
+        .add_systems(
+            (
+                tick_boss_spawn_timer,
+                spawn_boss_overtime,
+                boss_hit_by_star,
+                boss_spawn_minions,
+                handle_boss_defeated,
+                enemy_fire,
+                projectile_movement,
+                projectile_hit_player.after(enemy_hit_player),
+            )
+                .in_set(OnUpdate(AppState::Game)),
+        )
+        .add_systems(
+            (
+                camera_follow
+                    .after(player_hit_wall)
+                    .after(confine_player_movement),
+                update_offscreen_indicators.after(camera_follow),
+            )
+                .in_set(OnUpdate(AppState::Game)),
+        )
         .add_system(update_score)
-        .add_system(tick_star_spawn_timer)
-        .add_system(spawn_star_overtime)
-        .add_system(tick_enemy_spawn_timer)
-        .add_system(spawn_enemies_overtime)
-        .add_system(exit_game)
+        .add_system(update_score_hud)
+        .add_system(update_effects_hud)
+        .add_system(update_metrics_hud)
+        .add_system(spawn_boss_health_bar)
+        .add_system(update_boss_health_bar)
+        .add_system(update_dash_hud)
+        .add_system(toggle_fullscreen)
+        .add_system(apply_window_settings.after(toggle_fullscreen))
+        .add_system(fit_camera_to_window.after(apply_window_settings))
+        .add_system(apply_control_scheme)
+        .add_system(save_settings)
+        .add_system(apply_sprite_palette.in_base_set(CoreSet::PostUpdate))
+        .add_system(toggle_minimap)
+        .add_system(update_minimap.after(toggle_minimap))
         .add_system(handle_game_over)
+        .add_system(spawn_game_over_screen)
         .add_system(update_high_scores)
+        .add_system(save_finished_run.after(update_high_scores))
         .add_system(high_scores_updated)
+        .add_system(export_arena)
+        .add_system(spawn_wrap_ghosts)
+        .add_system(update_wrap_ghosts.after(spawn_wrap_ghosts))
+        .add_system(toggle_pause)
+        .add_system(pause_on_focus_lost)
+        .add_system(enter_pause.in_schedule(OnEnter(AppState::Paused)))
+        .add_system(exit_pause.in_schedule(OnExit(AppState::Paused)))
+        .add_system(enter_settings.in_schedule(OnEnter(AppState::Settings)))
+        .add_system(exit_settings.in_schedule(OnExit(AppState::Settings)))
+        .add_system(enter_resume_countdown.in_schedule(OnEnter(AppState::Resuming)))
+        .add_system(exit_resume_countdown.in_schedule(OnExit(AppState::Resuming)))
+        .add_system(unpause_time.in_schedule(OnEnter(AppState::Game)))
+        .add_systems(
+            (
+                reset_run,
+                spawn_player.after(reset_run),
+                spawn_enemies.after(reset_run),
+                spawn_stars.after(reset_run),
+            )
+                .in_schedule(OnEnter(AppState::Restarting)),
+        )
+        .add_system(finish_restart.in_set(OnUpdate(AppState::Restarting)))
+        .add_systems((menu_navigation, highlight_menu.after(menu_navigation)))
+        .add_system(
+            pause_menu_action
+                .after(menu_navigation)
+                .in_set(OnUpdate(AppState::Paused)),
+        )
+        .add_system(
+            settings_menu_action
+                .after(toggle_pause)
+                .after(menu_navigation)
+                .in_set(OnUpdate(AppState::Settings)),
+        )
+        .add_system(enter_confirm_quit.in_schedule(OnEnter(AppState::ConfirmQuit)))
+        .add_system(exit_confirm_quit.in_schedule(OnExit(AppState::ConfirmQuit)))
+        .add_system(
+            confirm_quit_action
+                .after(menu_navigation)
+                .in_set(OnUpdate(AppState::ConfirmQuit)),
+        )
+        .add_system(handle_close_requested)
+        .add_system(
+            shutdown
+                .after(pause_menu_action)
+                .after(confirm_quit_action)
+                .after(handle_close_requested),
+        )
+        .add_system(
+            update_settings_menu
+                .after(settings_menu_action)
+                .in_set(OnUpdate(AppState::Settings)),
+        )
+        .add_system(update_resume_countdown.in_set(OnUpdate(AppState::Resuming)))
+        .add_system(toggle_editor)
+        .add_system(enter_editor.in_schedule(OnEnter(AppState::Editor)))
+        .add_system(exit_editor.in_schedule(OnExit(AppState::Editor)))
+        .add_systems(
+            (
+                editor_keyboard,
+                editor_pan_camera,
+                editor_mouse.after(editor_pan_camera).after(editor_keyboard),
+                respawn_arena.after(editor_keyboard).after(editor_mouse),
+                draw_editor.after(editor_keyboard).after(editor_mouse),
+                update_editor_hud.after(editor_keyboard).after(editor_mouse),
+            )
+                .in_set(OnUpdate(AppState::Editor)),
+        )
         .run();
 }
+
+/*
+ function for picking the arena from the command line:
+ - `--generate [seed]` generates an arena from the seed, or from a random seed
+ - `<path>` loads that arena file
+ - nothing loads the default arena
+*/
+fn arena_from_args() -> (Arena, ArenaFile, RunSeed) {
+    let mut args = std::env::args().skip(1);
+    let first = args.next();
+
+    if first.as_deref() == Some("--generate") {
+        let seed = match args.next() {
+            Some(seed) => seed.parse().unwrap_or_else(|_| {
+                eprintln!("Invalid seed {}, using a random one", seed);
+                rand::random()
+            }),
+            None => rand::random(),
+        };
+        println!("Generating arena from seed {}", seed);
+        return (
+            generate_arena(seed),
+            ArenaFile {
+                path: generated_arena_path(seed),
+            },
+            RunSeed { value: Some(seed) },
+        );
+    }
+
+    let path = first.unwrap_or_else(|| ARENA_PATH.to_string());
+    (
+        Arena::load_or_default(&path),
+        ArenaFile { path },
+        RunSeed { value: None },
+    )
+}
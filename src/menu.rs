@@ -0,0 +1,651 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::window::{WindowCloseRequested, WindowFocused, WindowMode};
+
+use crate::components::*;
+use crate::events::Quit;
+use crate::hud::{HUD_FONT, HUD_FONT_SIZE};
+use crate::resources::*;
+use crate::states::AppState;
+
+pub const MENU_BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+pub const MENU_ITEM_COLOR: Color = Color::rgb(0.7, 0.7, 0.7);
+pub const MENU_SELECTED_COLOR: Color = Color::rgb(1.0, 0.9, 0.3);
+
+//entries of the pause menu, in the order they are shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseAction {
+    Resume,
+    Settings,
+    Restart,
+    Quit,
+}
+
+impl PauseAction {
+    pub const ALL: [PauseAction; 4] = [
+        PauseAction::Resume,
+        PauseAction::Settings,
+        PauseAction::Restart,
+        PauseAction::Quit,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PauseAction::Resume => "Resume",
+            PauseAction::Settings => "Settings",
+            PauseAction::Restart => "Restart",
+            PauseAction::Quit => "Quit",
+        }
+    }
+}
+
+//entries of the quit confirmation, in the order they are shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfirmQuitAction {
+    Quit,
+    Cancel,
+}
+
+impl ConfirmQuitAction {
+    pub const ALL: [ConfirmQuitAction; 2] = [ConfirmQuitAction::Quit, ConfirmQuitAction::Cancel];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConfirmQuitAction::Quit => "Quit",
+            ConfirmQuitAction::Cancel => "Cancel",
+        }
+    }
+}
+
+//entries of the settings menu, in the order they are shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingsItem {
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    ControlScheme,
+    Binding(KeyAction),
+    WindowMode,
+    VSync,
+    ColorblindPalette,
+    ReducedMotion,
+    Minimap,
+    Back,
+}
+
+impl SettingsItem {
+    pub const ALL: [SettingsItem; 16] = [
+        SettingsItem::MasterVolume,
+        SettingsItem::MusicVolume,
+        SettingsItem::SfxVolume,
+        SettingsItem::ControlScheme,
+        SettingsItem::Binding(KeyAction::Up),
+        SettingsItem::Binding(KeyAction::Down),
+        SettingsItem::Binding(KeyAction::Left),
+        SettingsItem::Binding(KeyAction::Right),
+        SettingsItem::Binding(KeyAction::Dash),
+        SettingsItem::Binding(KeyAction::Pause),
+        SettingsItem::WindowMode,
+        SettingsItem::VSync,
+        SettingsItem::ColorblindPalette,
+        SettingsItem::ReducedMotion,
+        SettingsItem::Minimap,
+        SettingsItem::Back,
+    ];
+
+    //text of the entry, showing the current value
+    pub fn label(&self, settings: &Settings, rebinding: Option<KeyAction>) -> String {
+        match *self {
+            SettingsItem::MasterVolume => volume_label("Master volume", settings.master_volume),
+            SettingsItem::MusicVolume => volume_label("Music volume", settings.music_volume),
+            SettingsItem::SfxVolume => volume_label("SFX volume", settings.sfx_volume),
+            SettingsItem::ControlScheme => {
+                format!("Control scheme: {:?}", settings.control_scheme)
+            }
+            SettingsItem::Binding(action) if rebinding == Some(action) => {
+                format!("{}: press a key", action.label())
+            }
+            SettingsItem::Binding(action) => {
+                let keys: Vec<String> = settings
+                    .key_bindings
+                    .keys(action)
+                    .iter()
+                    .map(|key| format!("{:?}", key))
+                    .collect();
+                format!("{}: {}", action.label(), keys.join(" / "))
+            }
+            SettingsItem::WindowMode => format!(
+                "Window mode: {}",
+                match settings.window_mode {
+                    WindowMode::Windowed => "Windowed",
+                    WindowMode::BorderlessFullscreen => "Borderless fullscreen",
+                    WindowMode::SizedFullscreen => "Sized fullscreen",
+                    WindowMode::Fullscreen => "Fullscreen",
+                }
+            ),
+            SettingsItem::VSync => toggle_label("VSync", settings.vsync),
+            SettingsItem::ColorblindPalette => {
+                toggle_label("Colourblind palette", settings.colorblind_palette)
+            }
+            SettingsItem::ReducedMotion => toggle_label("Reduced motion", settings.reduced_motion),
+            SettingsItem::Minimap => toggle_label("Minimap", settings.show_minimap),
+            SettingsItem::Back => "Back".to_string(),
+        }
+    }
+
+    //step a value up or down, toggles and choices go round whichever way they are stepped
+    pub fn change(&self, settings: &mut Settings, step: i32) {
+        match *self {
+            SettingsItem::MasterVolume => step_volume(&mut settings.master_volume, step),
+            SettingsItem::MusicVolume => step_volume(&mut settings.music_volume, step),
+            SettingsItem::SfxVolume => step_volume(&mut settings.sfx_volume, step),
+            SettingsItem::ControlScheme => {
+                settings.control_scheme = match settings.control_scheme {
+                    MovementModel::Direct => MovementModel::Inertial,
+                    MovementModel::Inertial => MovementModel::Direct,
+                }
+            }
+            SettingsItem::WindowMode => {
+                let modes = [
+                    WindowMode::Windowed,
+                    WindowMode::BorderlessFullscreen,
+                    WindowMode::Fullscreen,
+                ];
+                let index = modes
+                    .iter()
+                    .position(|mode| *mode == settings.window_mode)
+                    .unwrap_or(0) as i32;
+                settings.window_mode =
+                    modes[(index + step).rem_euclid(modes.len() as i32) as usize];
+            }
+            SettingsItem::VSync => settings.vsync = !settings.vsync,
+            SettingsItem::ColorblindPalette => {
+                settings.colorblind_palette = !settings.colorblind_palette
+            }
+            SettingsItem::ReducedMotion => settings.reduced_motion = !settings.reduced_motion,
+            SettingsItem::Minimap => settings.show_minimap = !settings.show_minimap,
+            SettingsItem::Binding(_) | SettingsItem::Back => {}
+        }
+    }
+}
+
+fn volume_label(name: &str, volume: f32) -> String {
+    format!("{}: {:.0}%", name, volume * 100.0)
+}
+
+fn toggle_label(name: &str, on: bool) -> String {
+    format!("{}: {}", name, if on { "on" } else { "off" })
+}
+
+//move a volume one step, counting in steps so it doesn't drift away from round numbers
+fn step_volume(volume: &mut f32, step: i32) {
+    let steps = (*volume / VOLUME_STEP).round() + step as f32;
+    *volume = (steps * VOLUME_STEP).clamp(0.0, 1.0);
+}
+
+//full screen overlay with a title and one line per menu entry
+fn spawn_menu(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    title: &str,
+    labels: Vec<String>,
+    marker: impl Component,
+) {
+    let text_style = TextStyle {
+        font: asset_server.load(HUD_FONT),
+        font_size: HUD_FONT_SIZE,
+        color: MENU_ITEM_COLOR,
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    gap: Size::height(Val::Px(8.0)),
+                    ..default()
+                },
+                background_color: MENU_BACKGROUND.into(),
+                ..default()
+            },
+            marker,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                title,
+                TextStyle {
+                    font_size: HUD_FONT_SIZE * 2.0,
+                    color: Color::WHITE,
+                    ..text_style.clone()
+                },
+            ));
+            for (index, label) in labels.into_iter().enumerate() {
+                parent.spawn((
+                    TextBundle::from_section(label, text_style.clone()),
+                    Interaction::default(),
+                    MenuButton { index },
+                ));
+            }
+        });
+}
+
+/*
+ system for pausing and going back with the pause key, with parameter
+ - keyboard_input and settings to know when the pause key is pressed
+ - rebinding to leave the pause key alone while the settings menu waits for a key
+ - app_state and next_state to move between playing, the pause menu and its screens
+*/
+pub fn toggle_pause(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    rebinding: Res<Rebinding>,
+    app_state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if rebinding.action.is_some()
+        || !settings
+            .key_bindings
+            .just_pressed(&keyboard_input, KeyAction::Pause)
+    {
+        return;
+    }
+    match app_state.0 {
+        AppState::Game | AppState::Resuming | AppState::Settings | AppState::ConfirmQuit => {
+            next_state.set(AppState::Paused)
+        }
+        AppState::Paused => next_state.set(AppState::Resuming),
+        _ => {}
+    }
+}
+
+//system for pausing when the window loses focus, so the game doesn't go on while the player is away
+pub fn pause_on_focus_lost(
+    mut focus_events: EventReader<WindowFocused>,
+    app_state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let focus_lost = focus_events.iter().any(|event| !event.focused);
+    if focus_lost && matches!(app_state.0, AppState::Game | AppState::Resuming) {
+        next_state.set(AppState::Paused);
+    }
+}
+
+/*
+ system for opening the pause menu, with parameter
+ - time to freeze everything that moves or counts down with it
+ - menu_cursor to start on the first entry
+*/
+pub fn enter_pause(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut time: ResMut<Time>,
+    mut menu_cursor: ResMut<MenuCursor>,
+) {
+    time.pause();
+    *menu_cursor = MenuCursor::default();
+
+    let labels = PauseAction::ALL
+        .iter()
+        .map(|action| action.label().to_string())
+        .collect();
+    spawn_menu(&mut commands, &asset_server, "Paused", labels, PauseMenu {});
+}
+
+//system for closing the pause menu
+pub fn exit_pause(mut commands: Commands, menu_query: Query<Entity, With<PauseMenu>>) {
+    for entity in menu_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/*
+ system for moving through the open menu with the arrow keys or the mouse, with parameter
+ - keyboard_input for up, down, left, right and enter
+ - button_query for the entries and whether the mouse is over or clicking one
+ - menu_cursor to remember the selected entry and report the chosen one
+ - rebinding to leave the keys alone while the settings menu waits for one
+*/
+pub fn menu_navigation(
+    keyboard_input: Res<Input<KeyCode>>,
+    button_query: Query<(&Interaction, &MenuButton)>,
+    mut menu_cursor: ResMut<MenuCursor>,
+    rebinding: Res<Rebinding>,
+) {
+    menu_cursor.chosen = None;
+    menu_cursor.step = 0;
+    let count = button_query.iter().count();
+    if count == 0 || rebinding.action.is_some() {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        menu_cursor.selected = (menu_cursor.selected + 1) % count;
+    }
+    if keyboard_input.just_pressed(KeyCode::Up) {
+        menu_cursor.selected = (menu_cursor.selected + count - 1) % count;
+    }
+    for (interaction, button) in button_query.iter() {
+        match interaction {
+            Interaction::Hovered => menu_cursor.selected = button.index,
+            Interaction::Clicked => {
+                menu_cursor.selected = button.index;
+                menu_cursor.chosen = Some(button.index);
+            }
+            Interaction::None => {}
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Right) {
+        menu_cursor.step += 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::Left) {
+        menu_cursor.step -= 1;
+    }
+    if keyboard_input.any_just_pressed([KeyCode::Return, KeyCode::Space]) {
+        menu_cursor.chosen = Some(menu_cursor.selected);
+    }
+}
+
+//system for highlighting the selected menu entry
+pub fn highlight_menu(
+    menu_cursor: Res<MenuCursor>,
+    mut button_query: Query<(&MenuButton, &mut Text)>,
+) {
+    for (button, mut text) in button_query.iter_mut() {
+        text.sections[0].style.color = if button.index == menu_cursor.selected {
+            MENU_SELECTED_COLOR
+        } else {
+            MENU_ITEM_COLOR
+        };
+    }
+}
+
+/*
+ system for acting on the chosen pause menu entry, with parameter
+ - menu_cursor for the chosen entry
+ - next_state to resume, open the settings or restart
+ - player_query to ask before quitting while a run is still going
+ - quit_event_writer to quit
+*/
+pub fn pause_menu_action(
+    menu_cursor: Res<MenuCursor>,
+    mut next_state: ResMut<NextState<AppState>>,
+    player_query: Query<(), With<Player>>,
+    mut quit_event_writer: EventWriter<Quit>,
+) {
+    let Some(index) = menu_cursor.chosen else {
+        return;
+    };
+    match PauseAction::ALL[index] {
+        PauseAction::Resume => next_state.set(AppState::Resuming),
+        PauseAction::Settings => next_state.set(AppState::Settings),
+        PauseAction::Restart => next_state.set(AppState::Restarting),
+        PauseAction::Quit if !player_query.is_empty() => next_state.set(AppState::ConfirmQuit),
+        PauseAction::Quit => quit_event_writer.send(Quit {}),
+    }
+}
+
+/*
+ system for asking before quitting, with parameter
+ - time to freeze the game, closing the window can get here without going through the pause menu
+ - menu_cursor to start on the first entry
+*/
+pub fn enter_confirm_quit(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut time: ResMut<Time>,
+    mut menu_cursor: ResMut<MenuCursor>,
+) {
+    time.pause();
+    *menu_cursor = MenuCursor::default();
+
+    let labels = ConfirmQuitAction::ALL
+        .iter()
+        .map(|action| action.label().to_string())
+        .collect();
+    spawn_menu(
+        &mut commands,
+        &asset_server,
+        "Quit and end this run?",
+        labels,
+        ConfirmQuitMenu {},
+    );
+}
+
+//system for closing the quit confirmation
+pub fn exit_confirm_quit(mut commands: Commands, menu_query: Query<Entity, With<ConfirmQuitMenu>>) {
+    for entity in menu_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/*
+ system for acting on the chosen quit confirmation entry, with parameter
+ - menu_cursor for the chosen entry
+ - next_state to go back to the pause menu
+ - quit_event_writer to quit
+*/
+pub fn confirm_quit_action(
+    menu_cursor: Res<MenuCursor>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut quit_event_writer: EventWriter<Quit>,
+) {
+    let Some(index) = menu_cursor.chosen else {
+        return;
+    };
+    match ConfirmQuitAction::ALL[index] {
+        ConfirmQuitAction::Quit => quit_event_writer.send(Quit {}),
+        ConfirmQuitAction::Cancel => next_state.set(AppState::Paused),
+    }
+}
+
+/*
+ system for closing the window the same way as quitting from the menu, with parameter
+ - close_events to know when the window's close button is pressed
+ - player_query to ask first while a run is still going
+ - next_state to open the quit confirmation
+ - quit_event_writer to quit
+*/
+pub fn handle_close_requested(
+    mut close_events: EventReader<WindowCloseRequested>,
+    player_query: Query<(), With<Player>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut quit_event_writer: EventWriter<Quit>,
+) {
+    if close_events.iter().last().is_none() {
+        return;
+    }
+    if player_query.is_empty() {
+        quit_event_writer.send(Quit {});
+    } else {
+        next_state.set(AppState::ConfirmQuit);
+    }
+}
+
+/*
+ system for opening the settings menu from the pause menu, with parameter
+ - settings to show the current values
+ - menu_cursor to start on the first entry
+ - rebinding to forget a key that was never pressed
+*/
+pub fn enter_settings(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+    mut menu_cursor: ResMut<MenuCursor>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    *menu_cursor = MenuCursor::default();
+    rebinding.action = None;
+
+    let labels = SettingsItem::ALL
+        .iter()
+        .map(|item| item.label(&settings, None))
+        .collect();
+    spawn_menu(
+        &mut commands,
+        &asset_server,
+        "Settings",
+        labels,
+        SettingsMenu {},
+    );
+}
+
+//system for closing the settings menu
+pub fn exit_settings(mut commands: Commands, menu_query: Query<Entity, With<SettingsMenu>>) {
+    for entity in menu_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/*
+ system for acting on the settings menu, with parameter
+ - menu_cursor for the chosen entry, and left or right to step the selected one
+ - keyboard_input for the new key while rebinding
+ - settings to change
+ - rebinding for the action waiting for a key
+ - next_state to go back to the pause menu
+*/
+pub fn settings_menu_action(
+    menu_cursor: Res<MenuCursor>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<Settings>,
+    mut rebinding: ResMut<Rebinding>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if let Some(action) = rebinding.action {
+        //escape gives up, any other key replaces the binding
+        if let Some(&key) = keyboard_input.get_just_pressed().next() {
+            if key != KeyCode::Escape {
+                *settings.key_bindings.keys_mut(action) = vec![key];
+            }
+            rebinding.action = None;
+        }
+        return;
+    }
+
+    let selected = SettingsItem::ALL[menu_cursor.selected];
+    if menu_cursor.step != 0 {
+        selected.change(&mut settings, menu_cursor.step);
+    }
+
+    let Some(index) = menu_cursor.chosen else {
+        return;
+    };
+    match SettingsItem::ALL[index] {
+        SettingsItem::Binding(action) => rebinding.action = Some(action),
+        SettingsItem::Back => next_state.set(AppState::Paused),
+        //choosing a full volume wraps it around to silent
+        SettingsItem::MasterVolume if settings.master_volume >= 1.0 => settings.master_volume = 0.0,
+        SettingsItem::MusicVolume if settings.music_volume >= 1.0 => settings.music_volume = 0.0,
+        SettingsItem::SfxVolume if settings.sfx_volume >= 1.0 => settings.sfx_volume = 0.0,
+        item => item.change(&mut settings, 1),
+    }
+}
+
+//system for showing the current values in the settings menu
+pub fn update_settings_menu(
+    settings: Res<Settings>,
+    rebinding: Res<Rebinding>,
+    mut button_query: Query<(&MenuButton, &mut Text)>,
+) {
+    if !settings.is_changed() && !rebinding.is_changed() {
+        return;
+    }
+    for (button, mut text) in button_query.iter_mut() {
+        text.sections[0].value = SettingsItem::ALL[button.index].label(&settings, rebinding.action);
+    }
+}
+
+/*
+ system for starting the countdown before play continues, with parameter
+ - resume_countdown to restart the countdown
+*/
+pub fn enter_resume_countdown(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut resume_countdown: ResMut<ResumeCountdown>,
+) {
+    resume_countdown.timer.reset();
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            CountdownText {},
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load(HUD_FONT),
+                    font_size: HUD_FONT_SIZE * 4.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+}
+
+/*
+ system for counting down to play, with parameter
+ - time for the real time that passed, the game time is still frozen
+ - resume_countdown to know how long is left
+ - text_query to show the seconds left
+ - next_state to start playing when it runs out
+*/
+pub fn update_resume_countdown(
+    time: Res<Time>,
+    mut resume_countdown: ResMut<ResumeCountdown>,
+    countdown_query: Query<&Children, With<CountdownText>>,
+    mut text_query: Query<&mut Text>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    resume_countdown
+        .timer
+        .tick(Duration::from_secs_f32(time.raw_delta_seconds()));
+    if resume_countdown.timer.finished() {
+        next_state.set(AppState::Game);
+        return;
+    }
+
+    let seconds_left = resume_countdown.timer.remaining_secs().ceil();
+    for children in countdown_query.iter() {
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                text.sections[0].value = format!("{}", seconds_left);
+            }
+        }
+    }
+}
+
+//system for removing the countdown
+pub fn exit_resume_countdown(
+    mut commands: Commands,
+    countdown_query: Query<Entity, With<CountdownText>>,
+) {
+    for entity in countdown_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+//system for letting time run again once play continues
+pub fn unpause_time(mut time: ResMut<Time>) {
+    time.unpause();
+}
+
+//system for moving on to the countdown once restart_run has set up the new run
+pub fn finish_restart(mut next_state: ResMut<NextState<AppState>>) {
+    next_state.set(AppState::Resuming);
+}
@@ -0,0 +1,203 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::asset::LoadState;
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::Deserialize;
+
+use crate::components::{Boss, Enemy};
+use crate::resources::Settings;
+use crate::save;
+use crate::states::AppState;
+use crate::systems::NUMBER_OF_ENEMIES;
+
+pub const MUSIC_MANIFEST_PATH: &str = "assets/music/manifest.ron";
+pub const MUSIC_CROSSFADE_RATE: f32 = 0.5; //how quickly the music follows the intensity, per second
+pub const MUSIC_DUCK_RATE: f32 = 4.0; //how quickly the music gets quieter or louder when pausing
+pub const PAUSED_MUSIC_LEVEL: f32 = 0.3; //music volume while the game isn't being played
+pub const DEFAULT_INTENSE_ENEMIES: usize = 40;
+
+//every music track, read from the music manifest
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct MusicManifest {
+    #[serde(default)]
+    pub tracks: Vec<MusicTrack>,
+}
+
+//track made of a calm and an intense loop of the same length, played together and crossfaded
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct MusicTrack {
+    pub name: String,
+    pub calm: String, //asset path
+    pub intense: String,
+    #[serde(default = "default_calm_enemies")]
+    pub calm_enemies: usize, //at or below this many enemies the track is fully calm
+    #[serde(default = "default_intense_enemies")]
+    pub intense_enemies: usize, //at or above this many enemies the track is fully intense
+}
+
+fn default_calm_enemies() -> usize {
+    NUMBER_OF_ENEMIES
+}
+
+fn default_intense_enemies() -> usize {
+    DEFAULT_INTENSE_ENEMIES
+}
+
+impl MusicTrack {
+    //0 when fully calm, 1 when fully intense
+    pub fn intensity(&self, enemies: usize) -> f32 {
+        if self.intense_enemies <= self.calm_enemies {
+            return if enemies > self.calm_enemies {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        let range = (self.intense_enemies - self.calm_enemies) as f32;
+        (enemies.saturating_sub(self.calm_enemies) as f32 / range).min(1.0)
+    }
+}
+
+//music track that is playing and how its layers are currently mixed
+#[derive(Resource, Default)]
+pub struct MusicPlayer {
+    pub track: Option<MusicTrack>,
+    pub calm: Handle<AudioSource>,
+    pub intense: Handle<AudioSource>,
+    pub sinks: Option<(Handle<AudioSink>, Handle<AudioSink>)>, //calm and intense, once they are playing
+    pub intensity: f32,
+    pub level: f32, //1 while playing, PAUSED_MUSIC_LEVEL while paused
+}
+
+//loops background music that gets more intense as the arena fills up
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MusicPlayer>()
+            .add_startup_system(load_music)
+            .add_system(start_music)
+            .add_system(mix_music.after(start_music));
+    }
+}
+
+//system for picking a track from the music manifest and loading its layers
+pub fn load_music(asset_server: Res<AssetServer>, mut music_player: ResMut<MusicPlayer>) {
+    let manifest = match save::load::<MusicManifest>(MUSIC_MANIFEST_PATH) {
+        Ok(manifest) => manifest.unwrap_or_default(),
+        Err(error) => {
+            eprintln!("Failed to load music manifest: {}", error);
+            MusicManifest::default()
+        }
+    };
+    let Some(track) = manifest.tracks.choose(&mut thread_rng()) else {
+        return;
+    };
+
+    println!("Playing {}", track.name);
+    music_player.calm = asset_server.load(track.calm.as_str());
+    music_player.intense = asset_server.load(track.intense.as_str());
+    music_player.track = Some(track.clone());
+    music_player.level = 1.0;
+}
+
+/*
+ system for starting both layers together once they are loaded, so they stay in time, with parameter
+ - asset_server to give up on a track whose layers failed to load
+ - audio_sources to know when both layers are loaded
+ - audio_sinks to keep hold of the playing layers
+*/
+pub fn start_music(
+    mut music_player: ResMut<MusicPlayer>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    audio_sources: Res<Assets<AudioSource>>,
+    audio_sinks: Res<Assets<AudioSink>>,
+) {
+    let Some(track) = music_player.track.as_ref() else {
+        return;
+    };
+    if music_player.sinks.is_some() {
+        return;
+    }
+    if asset_server.get_load_state(&music_player.calm) == LoadState::Failed
+        || asset_server.get_load_state(&music_player.intense) == LoadState::Failed
+    {
+        eprintln!("Failed to load music track {}", track.name);
+        music_player.track = None;
+        return;
+    }
+    if audio_sources.get(&music_player.calm).is_none()
+        || audio_sources.get(&music_player.intense).is_none()
+    {
+        return;
+    }
+
+    //both start silent, mix_music fades them in
+    let calm = audio.play_with_settings(
+        music_player.calm.clone(),
+        PlaybackSettings::LOOP.with_volume(0.0),
+    );
+    let intense = audio.play_with_settings(
+        music_player.intense.clone(),
+        PlaybackSettings::LOOP.with_volume(0.0),
+    );
+    music_player.sinks = Some((
+        audio_sinks.get_handle(calm),
+        audio_sinks.get_handle(intense),
+    ));
+}
+
+/*
+ system for crossfading the layers with the number of enemies, with parameter
+ - enemy_query and boss_query for how busy the arena is
+ - app_state to turn the music down when not playing
+ - settings for the music volume
+ - time for the real time that passed, the music keeps fading while the game is paused
+*/
+pub fn mix_music(
+    mut music_player: ResMut<MusicPlayer>,
+    audio_sinks: Res<Assets<AudioSink>>,
+    enemy_query: Query<(), With<Enemy>>,
+    boss_query: Query<(), With<Boss>>,
+    app_state: Res<State<AppState>>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let Some(track) = music_player.track.as_ref() else {
+        return;
+    };
+    let target_intensity = if boss_query.is_empty() {
+        track.intensity(enemy_query.iter().count())
+    } else {
+        1.0
+    };
+    let target_level = if app_state.0 == AppState::Game {
+        1.0
+    } else {
+        PAUSED_MUSIC_LEVEL
+    };
+
+    //close a fixed fraction of the gap every second, like the camera
+    let delta = time.raw_delta_seconds();
+    let crossfade = 1.0 - (-MUSIC_CROSSFADE_RATE * delta).exp();
+    let duck = 1.0 - (-MUSIC_DUCK_RATE * delta).exp();
+    music_player.intensity += (target_intensity - music_player.intensity) * crossfade;
+    music_player.level += (target_level - music_player.level) * duck;
+
+    let Some((calm, intense)) = music_player.sinks.as_ref() else {
+        return;
+    };
+    //equal power crossfade, so the music doesn't dip halfway through
+    let volume = settings.music_level() * music_player.level;
+    let angle = music_player.intensity * FRAC_PI_2;
+    if let Some(sink) = audio_sinks.get(calm) {
+        sink.set_volume(volume * angle.cos());
+    }
+    if let Some(sink) = audio_sinks.get(intense) {
+        sink.set_volume(volume * angle.sin());
+    }
+}
@@ -1,18 +1,85 @@
+use std::collections::HashMap;
+
+use bevy::ecs::system::Command;
 use bevy::prelude::*;
+use bevy::window::{PresentMode, WindowMode};
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::arena::Arena;
+use crate::components::*;
 
 pub const ENEMY_SPAWN_TIME: f32 = 5.0;
 pub const STAR_SPAWN_TIME: f32 = 1.0;
 
+pub const COMBO_WINDOW: f32 = 1.5; //seconds allowed between pickups to keep the combo going
+pub const COMBO_STEP: f32 = 0.5; //multiplier gained per consecutive pickup
+pub const MAX_COMBO_MULTIPLIER: f32 = 4.0;
+
 // resource score
 #[derive(Resource)]
 pub struct Score {
-    pub value: u32,
+    pub value: u32,        //total score
+    pub base_points: u32,  //points from the stars themselves
+    pub combo_bonus: u32,  //extra points from the combo multiplier
+    pub bonus_points: u32, //extra points from anything else, like power-ups
 }
 
 //memberi default value pada score agar dapat digunakan pada init_resource pada fungsi main, maksudnya langsung specify jenis resource dengan value asli dan nilai tidak hilang ketika diubah
 impl Default for Score {
     fn default() -> Score {
-        Score { value: 0 }
+        Score {
+            value: 0,
+            base_points: 0,
+            combo_bonus: 0,
+            bonus_points: 0,
+        }
+    }
+}
+
+impl Score {
+    pub fn add_base_points(&mut self, points: u32) {
+        self.base_points += points;
+        self.value += points;
+    }
+
+    pub fn add_combo_bonus(&mut self, points: u32) {
+        self.combo_bonus += points;
+        self.value += points;
+    }
+
+    pub fn add_bonus_points(&mut self, points: u32) {
+        self.bonus_points += points;
+        self.value += points;
+    }
+}
+
+//resource combo, consecutive star pickups within the combo window raise the multiplier
+#[derive(Resource)]
+pub struct Combo {
+    pub count: u32,   //number of consecutive pickups
+    pub timer: Timer, //time left before the combo decays one step
+}
+
+impl Default for Combo {
+    fn default() -> Combo {
+        Combo {
+            count: 0,
+            timer: Timer::from_seconds(COMBO_WINDOW, TimerMode::Repeating),
+        }
+    }
+}
+
+impl Combo {
+    //the first pickup has no bonus, every following one adds COMBO_STEP
+    pub fn multiplier(&self) -> f32 {
+        (1.0 + self.count.saturating_sub(1) as f32 * COMBO_STEP).min(MAX_COMBO_MULTIPLIER)
+    }
+
+    //register a pickup, restarting the combo window
+    pub fn hit(&mut self) {
+        self.count += 1;
+        self.timer.reset();
     }
 }
 
@@ -43,7 +110,7 @@ impl Default for EnemySpawnTimer {
     }
 }
 
-#[derive(Resource, Debug)]
+#[derive(Resource, Debug, Serialize, Deserialize)]
 pub struct HighScores {
     pub scores: Vec<(String, u32)>,
 }
@@ -53,3 +120,598 @@ impl Default for HighScores {
         HighScores { scores: Vec::new() }
     }
 }
+
+pub const POWER_UP_SPAWN_TIME: f32 = 8.0;
+
+#[derive(Resource)]
+pub struct PowerUpSpawnTimer {
+    pub timer: Timer,
+}
+//give default value
+impl Default for PowerUpSpawnTimer {
+    fn default() -> PowerUpSpawnTimer {
+        PowerUpSpawnTimer {
+            timer: Timer::from_seconds(POWER_UP_SPAWN_TIME, TimerMode::Repeating),
+        }
+    }
+}
+
+//weighted table deciding which power-up kind is spawned
+#[derive(Resource)]
+pub struct PowerUpSpawnTable {
+    pub entries: Vec<(PowerUpKind, f32)>, //(kind, weight)
+}
+
+impl Default for PowerUpSpawnTable {
+    fn default() -> PowerUpSpawnTable {
+        PowerUpSpawnTable {
+            entries: vec![
+                (PowerUpKind::Shield, 2.0),
+                (PowerUpKind::SpeedBoost, 3.0),
+                (PowerUpKind::SlowMotion, 2.0),
+                (PowerUpKind::StarMagnet, 3.0),
+                (PowerUpKind::ScoreMultiplier, 1.0),
+            ],
+        }
+    }
+}
+
+impl PowerUpSpawnTable {
+    //pick a kind at random according to the weights
+    pub fn choose(&self) -> Option<PowerUpKind> {
+        self.entries
+            .choose_weighted(&mut thread_rng(), |entry| entry.1)
+            .ok()
+            .map(|entry| entry.0)
+    }
+}
+
+//weighted table deciding which star kind is spawned
+#[derive(Resource)]
+pub struct StarSpawnTable {
+    pub entries: Vec<(StarKind, f32)>, //(kind, weight)
+}
+
+impl Default for StarSpawnTable {
+    fn default() -> StarSpawnTable {
+        StarSpawnTable {
+            entries: vec![
+                (StarKind::Common, 10.0),
+                (StarKind::Golden, 1.0),
+                (StarKind::Drifting, 3.0),
+                (StarKind::Fading, 3.0),
+            ],
+        }
+    }
+}
+
+impl StarSpawnTable {
+    //pick a kind at random according to the weights, falling back to a common star
+    pub fn choose(&self) -> StarKind {
+        self.entries
+            .choose_weighted(&mut thread_rng(), |entry| entry.1)
+            .map_or(StarKind::Common, |entry| entry.0)
+    }
+}
+
+pub const MAX_STARS: usize = 30;
+pub const MAX_ENEMIES: usize = 40;
+pub const MAX_SWARM_ENEMIES: usize = 160; //swarm members are counted apart from the other enemies
+pub const MAX_PROJECTILES: usize = 300;
+pub const MAX_POWER_UPS: usize = 5; //uncollected power-ups stay until picked up, so they are capped
+
+//maximum number of live stars, enemies, projectiles and power-ups
+#[derive(Resource)]
+pub struct EntityLimits {
+    pub max_stars: usize,
+    pub max_enemies: usize,
+    pub max_swarm_enemies: usize,
+    pub max_projectiles: usize,
+    pub max_power_ups: usize,
+}
+
+impl Default for EntityLimits {
+    fn default() -> EntityLimits {
+        EntityLimits {
+            max_stars: MAX_STARS,
+            max_enemies: MAX_ENEMIES,
+            max_swarm_enemies: MAX_SWARM_ENEMIES,
+            max_projectiles: MAX_PROJECTILES,
+            max_power_ups: MAX_POWER_UPS,
+        }
+    }
+}
+
+//hidden star and enemy sprites waiting to be reused instead of spawning new entities
+#[derive(Resource, Default)]
+pub struct EntityPool {
+    pub stars: Vec<Entity>,
+    pub enemies: Vec<Entity>,
+    pub projectiles: Vec<Entity>,
+}
+
+impl EntityPool {
+    //hide a star and keep it for later, removing the Star component so it stops being collectable
+    pub fn release_star(commands: &mut Commands, entity: Entity) {
+        commands.add(ReleaseToPool {
+            entity,
+            kind: PoolKind::Star,
+        });
+    }
+
+    //hide an enemy and keep it for later, removing the Enemy component so it stops moving and hitting
+    pub fn release_enemy(commands: &mut Commands, entity: Entity) {
+        commands.add(ReleaseToPool {
+            entity,
+            kind: PoolKind::Enemy,
+        });
+    }
+
+    //hide a projectile and keep it for later, removing the Projectile component so it stops moving and hitting
+    pub fn release_projectile(commands: &mut Commands, entity: Entity) {
+        commands.add(ReleaseToPool {
+            entity,
+            kind: PoolKind::Projectile,
+        });
+    }
+
+    pub fn pooled_count(&self) -> usize {
+        self.stars.len() + self.enemies.len() + self.projectiles.len()
+    }
+}
+
+//which list of the pool a released entity goes back to
+#[derive(Clone, Copy)]
+pub enum PoolKind {
+    Star,
+    Enemy,
+    Projectile,
+}
+
+/*
+ command hiding an entity and handing it back to the pool. The entity only joins the pool once its
+ component is gone, so a spawner running in the same frame can't take it and get its new component
+ removed by the release
+*/
+pub struct ReleaseToPool {
+    pub entity: Entity,
+    pub kind: PoolKind,
+}
+
+impl Command for ReleaseToPool {
+    fn write(self, world: &mut World) {
+        let Some(mut entity) = world.get_entity_mut(self.entity) else {
+            return;
+        };
+
+        //an entity released twice in one frame is only pooled once
+        let released = match self.kind {
+            PoolKind::Star => entity.take::<Star>().is_some(),
+            PoolKind::Enemy => entity.take::<Enemy>().is_some(),
+            PoolKind::Projectile => entity.take::<Projectile>().is_some(),
+        };
+        if !released {
+            return;
+        }
+        entity.insert(Visibility::Hidden);
+
+        let mut entity_pool = world.resource_mut::<EntityPool>();
+        match self.kind {
+            PoolKind::Star => entity_pool.stars.push(self.entity),
+            PoolKind::Enemy => entity_pool.enemies.push(self.entity),
+            PoolKind::Projectile => entity_pool.projectiles.push(self.entity),
+        }
+    }
+}
+
+//weighted table deciding which enemy kind is spawned
+#[derive(Resource)]
+pub struct EnemySpawnTable {
+    pub entries: Vec<(EnemyKind, f32)>, //(kind, weight)
+}
+
+impl Default for EnemySpawnTable {
+    fn default() -> EnemySpawnTable {
+        EnemySpawnTable {
+            entries: vec![
+                (EnemyKind::Bouncer, 6.0),
+                (EnemyKind::Chaser, 2.0),
+                (EnemyKind::Wanderer, 3.0),
+                (EnemyKind::Orbiter, 2.0),
+            ],
+        }
+    }
+}
+
+impl EnemySpawnTable {
+    //pick a kind at random according to the weights, falling back to a bouncer
+    pub fn choose(&self) -> EnemyKind {
+        self.entries
+            .choose_weighted(&mut thread_rng(), |entry| entry.1)
+            .map_or(EnemyKind::Bouncer, |entry| entry.0)
+    }
+}
+
+pub const SWARM_SPAWN_TIME: f32 = 45.0; //the first swarm shows up late, then one every SWARM_SPAWN_TIME
+pub const SWARM_NEIGHBOUR_RADIUS: f32 = 90.0;
+
+#[derive(Resource)]
+pub struct SwarmSpawnTimer {
+    pub timer: Timer,
+}
+//give default value
+impl Default for SwarmSpawnTimer {
+    fn default() -> SwarmSpawnTimer {
+        SwarmSpawnTimer {
+            timer: Timer::from_seconds(SWARM_SPAWN_TIME, TimerMode::Repeating),
+        }
+    }
+}
+
+//uniform grid of swarm members, rebuilt every frame so neighbour lookups only look at nearby cells
+#[derive(Resource, Default)]
+pub struct SwarmGrid {
+    pub cells: HashMap<IVec2, Vec<(Vec2, Vec2)>>, //cell -> (position, direction) of the members inside
+}
+
+impl SwarmGrid {
+    pub fn cell(position: Vec2) -> IVec2 {
+        (position / SWARM_NEIGHBOUR_RADIUS).floor().as_ivec2()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    pub fn insert(&mut self, position: Vec2, direction: Vec2) {
+        self.cells
+            .entry(SwarmGrid::cell(position))
+            .or_default()
+            .push((position, direction));
+    }
+
+    //every member within SWARM_NEIGHBOUR_RADIUS of the position, including the member at that position
+    pub fn neighbours(&self, position: Vec2) -> impl Iterator<Item = &(Vec2, Vec2)> {
+        let cell = SwarmGrid::cell(position);
+        (-1..=1)
+            .flat_map(move |x| (-1..=1).map(move |y| cell + IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter(move |(other, _)| {
+                other.distance_squared(position) < SWARM_NEIGHBOUR_RADIUS * SWARM_NEIGHBOUR_RADIUS
+            })
+    }
+}
+
+pub const BOSS_SPAWN_TIME: f32 = 90.0;
+
+#[derive(Resource)]
+pub struct BossSpawnTimer {
+    pub timer: Timer,
+}
+//give default value
+impl Default for BossSpawnTimer {
+    fn default() -> BossSpawnTimer {
+        BossSpawnTimer {
+            timer: Timer::from_seconds(BOSS_SPAWN_TIME, TimerMode::Repeating),
+        }
+    }
+}
+
+pub const PLAYER_ACCELERATION: f32 = 2000.0;
+pub const PLAYER_MAX_SPEED: f32 = 500.0;
+pub const PLAYER_FRICTION: f32 = 4.0; //fraction of the velocity lost per second without input
+pub const PLAYER_RESTITUTION: f32 = 0.8; //fraction of the speed kept when bouncing off an edge
+
+//how the player's input turns into movement
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MovementModel {
+    Direct,   //full speed instantly, stops dead on release
+    Inertial, //accelerates, slides and bounces like a ball
+}
+
+#[derive(Resource)]
+pub struct MovementSettings {
+    pub model: MovementModel,
+    pub acceleration: f32,
+    pub max_speed: f32,
+    pub friction: f32,
+    pub restitution: f32,
+}
+
+impl Default for MovementSettings {
+    fn default() -> MovementSettings {
+        MovementSettings {
+            model: MovementModel::Direct,
+            acceleration: PLAYER_ACCELERATION,
+            max_speed: PLAYER_MAX_SPEED,
+            friction: PLAYER_FRICTION,
+            restitution: PLAYER_RESTITUTION,
+        }
+    }
+}
+
+pub const EDITOR_HISTORY_LIMIT: usize = 100; //oldest undo steps are dropped past this
+
+//what a click on empty space in the editor places
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditorTool {
+    RectWall,
+    CircleWall,
+    EnemyRegion,
+    StarRegion,
+    PlayerStart,
+}
+
+impl EditorTool {
+    pub fn label(&self) -> &'static str {
+        match self {
+            EditorTool::RectWall => "Rect wall",
+            EditorTool::CircleWall => "Circle wall",
+            EditorTool::EnemyRegion => "Enemy region",
+            EditorTool::StarRegion => "Star region",
+            EditorTool::PlayerStart => "Player start",
+        }
+    }
+}
+
+//something in the arena the editor can select, indexes point into the arena lists
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditorItem {
+    Wall(usize),
+    EnemyRegion(usize),
+    StarRegion(usize),
+    PlayerStart,
+}
+
+//what the mouse is doing while the left button is held
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EditorDrag {
+    Move { item: EditorItem, last: Vec2 }, //last snapped cursor position
+    Resize { item: EditorItem, anchor: Vec2 }, //anchor is the corner or center that stays put
+}
+
+#[derive(Resource)]
+pub struct EditorState {
+    pub tool: EditorTool,
+    pub snap: bool,
+    pub selected: Option<EditorItem>,
+    pub drag: Option<EditorDrag>,
+    pub before_drag: Option<Arena>, //arena as it was when the drag started, for undo
+    pub undo: Vec<Arena>,
+    pub redo: Vec<Arena>,
+    pub message: String, //result of the last save
+}
+
+//give default value
+impl Default for EditorState {
+    fn default() -> EditorState {
+        EditorState {
+            tool: EditorTool::RectWall,
+            snap: true,
+            selected: None,
+            drag: None,
+            before_drag: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            message: String::new(),
+        }
+    }
+}
+
+impl EditorState {
+    //remember the arena before an edit so it can be undone
+    pub fn record(&mut self, before: Arena) {
+        self.undo.push(before);
+        if self.undo.len() > EDITOR_HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    //stop dragging without recording anything, the dragged item may not exist anymore
+    pub fn cancel_drag(&mut self) {
+        self.drag = None;
+        self.before_drag = None;
+    }
+
+    pub fn undo(&mut self, arena: &mut Arena) {
+        if let Some(previous) = self.undo.pop() {
+            self.redo.push(std::mem::replace(arena, previous));
+            self.selected = None;
+            self.cancel_drag();
+        }
+    }
+
+    pub fn redo(&mut self, arena: &mut Arena) {
+        if let Some(next) = self.redo.pop() {
+            self.undo.push(std::mem::replace(arena, next));
+            self.selected = None;
+            self.cancel_drag();
+        }
+    }
+}
+
+//seed the arena was generated from, it only decides the arena layout and is None for arenas loaded from a file
+#[derive(Resource)]
+pub struct RunSeed {
+    pub value: Option<u64>,
+}
+
+pub const VOLUME_STEP: f32 = 0.1; //how much one press changes a volume in the settings menu
+
+//player facing settings, saved to the settings file whenever they change
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub master_volume: f32, //every volume is between 0 and 1
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub control_scheme: MovementModel,
+    pub key_bindings: KeyBindings,
+    pub window_mode: WindowMode,
+    pub vsync: bool,
+    pub colorblind_palette: bool,
+    pub reduced_motion: bool, //no afterimages, drifting popups or flashing stars
+    pub show_minimap: bool,
+}
+
+//give default value
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            master_volume: 1.0,
+            music_volume: 0.7,
+            sfx_volume: 1.0,
+            control_scheme: MovementModel::Direct,
+            key_bindings: KeyBindings::default(),
+            window_mode: WindowMode::Windowed,
+            vsync: true,
+            colorblind_palette: false,
+            reduced_motion: false,
+            show_minimap: true,
+        }
+    }
+}
+
+impl Settings {
+    //volume of sound effects after the master volume
+    pub fn sfx_level(&self) -> f32 {
+        self.master_volume * self.sfx_volume
+    }
+
+    //volume of music after the master volume
+    pub fn music_level(&self) -> f32 {
+        self.master_volume * self.music_volume
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        if self.vsync {
+            PresentMode::AutoVsync
+        } else {
+            PresentMode::AutoNoVsync
+        }
+    }
+}
+
+//something the player can do with a key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyAction {
+    Up,
+    Down,
+    Left,
+    Right,
+    Dash,
+    Pause,
+}
+
+impl KeyAction {
+    pub const ALL: [KeyAction; 6] = [
+        KeyAction::Up,
+        KeyAction::Down,
+        KeyAction::Left,
+        KeyAction::Right,
+        KeyAction::Dash,
+        KeyAction::Pause,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeyAction::Up => "Move up",
+            KeyAction::Down => "Move down",
+            KeyAction::Left => "Move left",
+            KeyAction::Right => "Move right",
+            KeyAction::Dash => "Dash",
+            KeyAction::Pause => "Pause",
+        }
+    }
+}
+
+//keys bound to every action, any of them works
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub up: Vec<KeyCode>,
+    pub down: Vec<KeyCode>,
+    pub left: Vec<KeyCode>,
+    pub right: Vec<KeyCode>,
+    pub dash: Vec<KeyCode>,
+    pub pause: Vec<KeyCode>,
+}
+
+//give default value
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            up: vec![KeyCode::Up, KeyCode::W],
+            down: vec![KeyCode::Down, KeyCode::S],
+            left: vec![KeyCode::Left, KeyCode::A],
+            right: vec![KeyCode::Right, KeyCode::D],
+            dash: vec![KeyCode::Space],
+            pause: vec![KeyCode::Escape],
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn keys(&self, action: KeyAction) -> &Vec<KeyCode> {
+        match action {
+            KeyAction::Up => &self.up,
+            KeyAction::Down => &self.down,
+            KeyAction::Left => &self.left,
+            KeyAction::Right => &self.right,
+            KeyAction::Dash => &self.dash,
+            KeyAction::Pause => &self.pause,
+        }
+    }
+
+    pub fn keys_mut(&mut self, action: KeyAction) -> &mut Vec<KeyCode> {
+        match action {
+            KeyAction::Up => &mut self.up,
+            KeyAction::Down => &mut self.down,
+            KeyAction::Left => &mut self.left,
+            KeyAction::Right => &mut self.right,
+            KeyAction::Dash => &mut self.dash,
+            KeyAction::Pause => &mut self.pause,
+        }
+    }
+
+    pub fn pressed(&self, keyboard_input: &Input<KeyCode>, action: KeyAction) -> bool {
+        keyboard_input.any_pressed(self.keys(action).iter().copied())
+    }
+
+    pub fn just_pressed(&self, keyboard_input: &Input<KeyCode>, action: KeyAction) -> bool {
+        keyboard_input.any_just_pressed(self.keys(action).iter().copied())
+    }
+}
+
+pub const RESUME_COUNTDOWN_TIME: f32 = 3.0; //seconds counted down before play continues
+
+//action waiting for a key to be pressed in the settings menu
+#[derive(Resource, Default)]
+pub struct Rebinding {
+    pub action: Option<KeyAction>,
+}
+
+//selected entry of the open menu, and the entry chosen this frame if any
+#[derive(Resource, Default)]
+pub struct MenuCursor {
+    pub selected: usize,
+    pub chosen: Option<usize>,
+    pub step: i32, //-1 or 1 when left or right is pressed this frame
+}
+
+//countdown shown before play continues after a pause
+#[derive(Resource)]
+pub struct ResumeCountdown {
+    pub timer: Timer,
+}
+
+//give default value
+impl Default for ResumeCountdown {
+    fn default() -> ResumeCountdown {
+        ResumeCountdown {
+            timer: Timer::from_seconds(RESUME_COUNTDOWN_TIME, TimerMode::Once),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::arena::Arena;
+use crate::components::Player;
+use crate::events::{GameOver, Quit};
+use crate::resources::*;
+
+pub const HIGH_SCORES_PATH: &str = "saves/high_scores.ron";
+pub const SETTINGS_PATH: &str = "saves/settings.ron";
+pub const LAST_RUN_PATH: &str = "saves/last_run.ron";
+
+//error from reading or writing a save file, each one keeps the path of the file it came from
+#[derive(Debug)]
+pub enum SaveError {
+    Io {
+        path: String,
+        error: std::io::Error,
+    },
+    Parse {
+        path: String,
+        error: ron::error::SpannedError,
+    },
+    Serialize {
+        path: String,
+        error: ron::Error,
+    },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io { path, error } => write!(f, "{}: {}", path, error),
+            SaveError::Parse { path, error } => write!(
+                f,
+                "{}:{}:{}: {}",
+                path, error.position.line, error.position.col, error.code
+            ),
+            SaveError::Serialize { path, error } => write!(f, "{}: {}", path, error),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+//read a save file, None when it doesn't exist yet
+pub fn load<T: DeserializeOwned>(path: &str) -> Result<Option<T>, SaveError> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => {
+            return Err(SaveError::Io {
+                path: path.to_string(),
+                error,
+            })
+        }
+    };
+    ron::from_str(&source)
+        .map(Some)
+        .map_err(|error| SaveError::Parse {
+            path: path.to_string(),
+            error,
+        })
+}
+
+//read a save file, reporting any error and falling back to the default value
+pub fn load_or_default<T: DeserializeOwned + Default>(path: &str) -> T {
+    match load(path) {
+        Ok(value) => value.unwrap_or_default(),
+        Err(error) => {
+            eprintln!("Failed to load save file: {}", error);
+            T::default()
+        }
+    }
+}
+
+//write a value to a save file as pretty printed RON, creating the save directory if needed
+pub fn save<T: Serialize>(path: &str, value: &T) -> Result<(), SaveError> {
+    if let Some(directory) = Path::new(path).parent() {
+        fs::create_dir_all(directory).map_err(|error| SaveError::Io {
+            path: path.to_string(),
+            error,
+        })?;
+    }
+    let source = ron::ser::to_string_pretty(value, PrettyConfig::default()).map_err(|error| {
+        SaveError::Serialize {
+            path: path.to_string(),
+            error,
+        }
+    })?;
+    fs::write(path, source).map_err(|error| SaveError::Io {
+        path: path.to_string(),
+        error,
+    })
+}
+
+//score and stats of the last run, written when it ends or when the game quits mid-run
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RunRecord {
+    pub arena: String,
+    pub seed: Option<u64>, //seed the arena was generated from, None for arenas loaded from a file
+    pub score: u32,
+    pub base_points: u32,
+    pub combo_bonus: u32,
+    pub bonus_points: u32,
+}
+
+impl RunRecord {
+    pub fn new(arena: &Arena, run_seed: &RunSeed, score: &Score) -> RunRecord {
+        RunRecord {
+            arena: arena.name.clone(),
+            seed: run_seed.value,
+            score: score.value,
+            base_points: score.base_points,
+            combo_bonus: score.combo_bonus,
+            bonus_points: score.bonus_points,
+        }
+    }
+}
+
+/*
+ system for writing the run and the high scores as soon as the game is over, so they survive a crash, with parameter
+ - game_over_event_reader to know when a run ended
+ - score, arena and run_seed to record that run
+ - high_scores, which already hold the run's score
+*/
+pub fn save_finished_run(
+    mut game_over_event_reader: EventReader<GameOver>,
+    score: Res<Score>,
+    arena: Res<Arena>,
+    run_seed: Res<RunSeed>,
+    high_scores: Res<HighScores>,
+) {
+    if game_over_event_reader.iter().last().is_none() {
+        return;
+    }
+
+    if let Err(error) = save(LAST_RUN_PATH, &RunRecord::new(&arena, &run_seed, &score)) {
+        eprintln!("Failed to save run: {}", error);
+    }
+    if let Err(error) = save(HIGH_SCORES_PATH, &*high_scores) {
+        eprintln!("Failed to save high scores: {}", error);
+    }
+}
+
+/*
+ system for shutting down once quitting is confirmed, with parameter
+ - quit_event_reader to know when to quit
+ - player_query to know whether a run is still going
+ - score, arena and run_seed to record that run
+ - high_scores and settings to write to disk
+ - app_exit_event_writer to close the app once everything is saved
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn shutdown(
+    mut quit_event_reader: EventReader<Quit>,
+    player_query: Query<(), With<Player>>,
+    score: Res<Score>,
+    arena: Res<Arena>,
+    run_seed: Res<RunSeed>,
+    mut high_scores: ResMut<HighScores>,
+    settings: Res<Settings>,
+    mut app_exit_event_writer: EventWriter<AppExit>,
+) {
+    if quit_event_reader.iter().last().is_none() {
+        return;
+    }
+
+    //a run that is still going counts as ended here
+    if !player_query.is_empty() {
+        high_scores.scores.push(("Player".to_string(), score.value));
+        let record = RunRecord::new(&arena, &run_seed, &score);
+        if let Err(error) = save(LAST_RUN_PATH, &record) {
+            eprintln!("Failed to save run: {}", error);
+        }
+    }
+
+    if let Err(error) = save(HIGH_SCORES_PATH, &*high_scores) {
+        eprintln!("Failed to save high scores: {}", error);
+    }
+    if let Err(error) = save(SETTINGS_PATH, &*settings) {
+        eprintln!("Failed to save settings: {}", error);
+    }
+    app_exit_event_writer.send(AppExit);
+}
+
+//system for writing the settings to the settings file whenever they change
+pub fn save_settings(settings: Res<Settings>) {
+    if settings.is_changed() && !settings.is_added() {
+        if let Err(error) = save(SETTINGS_PATH, &*settings) {
+            eprintln!("Failed to save settings: {}", error);
+        }
+    }
+}
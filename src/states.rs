@@ -0,0 +1,14 @@
+use bevy::prelude::*;
+
+//top level state of the app, gameplay systems only run while playing
+#[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum AppState {
+    #[default]
+    Game,
+    Editor,      //arena editor, the game is frozen and hidden
+    Paused,      //pause menu over the frozen game
+    Settings,    //settings menu, opened from the pause menu
+    Resuming,    //countdown before play continues, the game is still frozen
+    Restarting,  //clearing the run and setting up a new one
+    ConfirmQuit, //asking before a run that is still going is ended by quitting
+}
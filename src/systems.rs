@@ -1,273 +1,946 @@
-use bevy::app::AppExit;
 use bevy::prelude::*;
-use bevy::window::PrimaryWindow;
+use bevy::sprite::MaterialMesh2dBundle;
 use rand::prelude::*; //for quiting app
 
+use crate::arena::*;
 use crate::components::*;
 use crate::events::*;
+use crate::hud::{HUD_FONT, HUD_FONT_SIZE};
 use crate::resources::*;
 
 // player's variable
 pub const PLAYER_SPEED: f32 = 500.0;
 pub const PLAYER_SIZE: f32 = 64.0; //player sprite size
+pub const DASH_SPEED_MULTIPLIER: f32 = 3.5;
+pub const AFTERIMAGE_TIME: f32 = 0.25; //how long an afterimage takes to fade out
 
 //enemy's variable
 pub const NUMBER_OF_ENEMIES: usize = 4;
-pub const ENEMY_SPEED: f32 = 200.0;
-pub const ENEMY_SIZE: f32 = 64.0; //enemy sprite size
+pub const CHASER_TURN_RATE: f32 = 1.5; //radians per second
+pub const WANDER_TURN_RATE: f32 = 4.0; //maximum random turn in radians per second
+pub const ORBIT_RADIUS: f32 = 120.0;
+
+//swarm variable
+pub const SWARM_WAVE_SIZE: usize = 40;
+pub const SWARM_SEPARATION_RADIUS: f32 = 40.0;
+pub const SWARM_SEPARATION_WEIGHT: f32 = 3.0;
+pub const SWARM_ALIGNMENT_WEIGHT: f32 = 1.0;
+pub const SWARM_COHESION_WEIGHT: f32 = 0.8;
+pub const SWARM_PLAYER_PULL_WEIGHT: f32 = 0.3; //weak pull so the swarm drifts toward the player
+pub const SWARM_STEERING_RATE: f32 = 3.0; //how quickly members turn toward the steering direction
+
+//boss variable
+pub const BOSS_DAMAGE_RADIUS: f32 = 200.0; //stars collected this close to the boss damage it
+pub const BOSS_DAMAGE_PER_POINT: f32 = 1.0;
+pub const BOSS_BONUS: u32 = 100;
+pub const BOSS_MINIONS_PER_PHASE: usize = 3;
+
+//projectile variable
+pub const PROJECTILE_SIZE: f32 = 16.0; //projectile sprite size
+
+//wall variable
+pub const WALL_COLOR: Color = Color::rgb(0.35, 0.35, 0.4);
+pub const BACKGROUND_Z: f32 = -10.0; //background image sits behind every sprite
+pub const CAMERA_Z: f32 = 500.0; //middle of the camera depth range, so sprites above and below z = 0 are both drawn
+pub const CAMERA_FOLLOW_RATE: f32 = 5.0; //how quickly the camera catches up with the player
+pub const OUTSIDE_ARENA_SHADE: f32 = 0.5; //how much darker it is outside a circular arena
+pub const OPEN_POSITION_ATTEMPTS: usize = 20; //tries before giving up on finding a spot outside the walls
+
+//graze variable
+pub const GRAZE_DISTANCE: f32 = 40.0; //width of the graze ring outside the collision distance
+pub const GRAZE_POINTS: u32 = 2;
+pub const GRAZE_COOLDOWN: f32 = 3.0; //seconds before the same enemy can be grazed again
+pub const FLOATING_TEXT_TIME: f32 = 0.8;
+pub const FLOATING_TEXT_SPEED: f32 = 60.0;
 
 //star variable
 pub const NUMBER_OF_STARS: usize = 10;
 pub const STAR_SIZE: f32 = 30.0; //star sprite size
+pub const STAR_FLASH_TIME: f32 = 2.0; //expiring stars flash during their last seconds
+pub const STAR_FLASH_RATE: f32 = 8.0; //flashes per second
+
+//power-up variable
+pub const POWER_UP_SIZE: f32 = 32.0; //power-up sprite size
+pub const SPEED_BOOST_MULTIPLIER: f32 = 1.6;
+pub const SLOW_MOTION_FACTOR: f32 = 0.4; //enemy speed is multiplied by this while slow-motion is active
+pub const STAR_MAGNET_RADIUS: f32 = 250.0;
+pub const STAR_MAGNET_SPEED: f32 = 350.0;
 
 /*
 system untuk spawn player dengan parameter
  - commands untuk memberikan command,
- - window_query untuk mendapatkan width dan height pada window,
+ - arena to get the player start,
  - resources asset_server untuk menggunakan asset
  */
 pub fn spawn_player(
     mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    arena: Res<Arena>,
     asset_server: Res<AssetServer>, //resource yang akan digunakan untuk laod asset
 ) {
-    let window = window_query.get_single().unwrap(); //mendapatkan referensi pada window
-
-    //spawning entity in the midle of the screen, with bundle as parameter
+    //spawning entity at the arena's player start, with bundle as parameter
     //spawning, must be inside bundle, since we write more than one component
     commands.spawn((
         SpriteBundle {
-            transform: Transform::from_xyz(window.width() / 2.0, window.height() / 2.0, 0.0), //set of player position to the middle of the screen
-            texture: asset_server.load("sprites/ball_blue_large.png"), //load this asset
+            transform: Transform::from_translation(arena.player_start.extend(0.0)),
+            texture: asset_server.load(PLAYER_TEXTURE), //load this asset
             ..default()
         },
-        Player {},
+        Player::default(),
+        Velocity::default(),
+        ActiveEffects::default(),
+        Dash::default(),
     ));
 }
 
 /*
  system untuk spawn enemy dengan parameter
  - commands untuk memberikan command,
- - window_query untuk mendapatkan width dan height pada window,
+ - arena untuk mendapatkan width dan height pada arena,
  - resources asset_server untuk menggunakan asset
 */
 pub fn spawn_enemies(
     mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    arena: Res<Arena>,
     asset_server: Res<AssetServer>, //resource yang akan digunakan untuk laod asset
+    mut entity_pool: ResMut<EntityPool>,
+    enemy_spawn_table: Res<EnemySpawnTable>,
+    wall_query: Query<(&Transform, &Wall)>,
 ) {
-    let window = window_query.get_single().unwrap(); //mendapatkan referensi pada window
-
     //spawn enemy
     for _ in 0..NUMBER_OF_ENEMIES {
-        //get random position
-        let random_x = random::<f32>() * window.width();
-        let random_y = random::<f32>() * window.height();
+        //get random position outside the walls
+        let position = random_open_position(
+            &arena,
+            &arena.enemy_spawn_regions,
+            &wall_query,
+            ENEMY_SIZE / 2.0,
+        );
 
-        //spawning, must be inside bundle, since we write more than one component
+        spawn_enemy(
+            &mut commands,
+            &mut entity_pool,
+            &asset_server,
+            position,
+            enemy_spawn_table.choose(),
+        );
+    }
+}
+
+/*
+ function for spawning a single enemy, reusing a pooled entity when there is one, with parameter
+ - commands untuk memberikan command,
+ - entity_pool to take a hidden enemy from
+ - asset_server untuk menggunakan asset
+ - position where the enemy is placed
+ - kind of enemy to spawn
+*/
+pub fn spawn_enemy(
+    commands: &mut Commands,
+    entity_pool: &mut EntityPool,
+    asset_server: &AssetServer,
+    position: Vec3,
+    kind: EnemyKind,
+) {
+    let mut enemy = Enemy::new(
+        kind,
+        Vec2::new(random::<f32>(), random::<f32>()).normalize(), //give diraction random value and then normalize
+    );
+    enemy.anchor = position.truncate(); //orbiters circle around where they spawned
+    let texture: Handle<Image> = asset_server.load(kind.texture());
+    let sprite = Sprite {
+        custom_size: Some(Vec2::splat(kind.size())),
+        ..default()
+    };
+
+    //reuse a hidden enemy if there is one
+    if let Some(entity) = entity_pool.enemies.pop() {
+        commands.entity(entity).insert((
+            sprite,
+            Transform::from_translation(position),
+            texture,
+            Visibility::Inherited,
+            enemy,
+        ));
+        return;
+    }
+
+    //spawning, must be inside bundle, since we write more than one component
+    commands.spawn((
+        SpriteBundle {
+            sprite,
+            transform: Transform::from_translation(position),
+            texture,
+            ..default()
+        },
+        enemy,
+    ));
+}
+
+/*
+system untuk spawn arena (background and walls) dengan parameter
+ - commands untuk memberikan command,
+ - arena for the loaded layout,
+ - asset_server untuk menggunakan asset
+*/
+pub fn spawn_arena(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    spawn_arena_floor(
+        &mut commands,
+        &arena,
+        &asset_server,
+        &mut meshes,
+        &mut materials,
+    );
+
+    for wall in arena.walls.iter() {
+        spawn_wall(&mut commands, &asset_server, wall.center(), wall.shape());
+    }
+}
+
+/*
+ function for setting up what is behind everything else, with parameter
+ - arena for the background and boundary
+ - meshes and materials for the disk of a circular arena
+*/
+pub fn spawn_arena_floor(
+    commands: &mut Commands,
+    arena: &Arena,
+    asset_server: &AssetServer,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+) {
+    let (r, g, b) = arena.background.color;
+    let color = Color::rgb(r, g, b);
+
+    //the floor is drawn rather than cleared to, so the letterbox around the view keeps its own color
+    let floor_color = if arena.boundary == Boundary::Circle {
+        //outside the rim is darker, the disk itself has the background color
+        Color::rgb(
+            r * OUTSIDE_ARENA_SHADE,
+            g * OUTSIDE_ARENA_SHADE,
+            b * OUTSIDE_ARENA_SHADE,
+        )
+    } else {
+        color
+    };
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: floor_color,
+                custom_size: Some(arena.size),
+                ..default()
+            },
+            transform: Transform::from_translation(arena.center().extend(BACKGROUND_Z - 2.0)),
+            ..default()
+        },
+        ArenaFloor {},
+    ));
+
+    if arena.boundary == Boundary::Circle {
         commands.spawn((
-            SpriteBundle {
-                transform: Transform::from_xyz(random_x, random_y, 0.0),
-                texture: asset_server.load("sprites/ball_red_large.png"),
+            MaterialMesh2dBundle {
+                mesh: meshes.add(shape::Circle::new(arena.radius()).into()).into(),
+                material: materials.add(ColorMaterial::from(color)),
+                transform: Transform::from_translation(arena.center().extend(BACKGROUND_Z - 1.0)),
                 ..default()
             },
-            Enemy {
-                direction: Vec2::new(random::<f32>(), random::<f32>()).normalize(), //give diraction random value and then normalize
+            ArenaFloor {},
+        ));
+    }
+
+    //background image is stretched over the whole arena, behind everything else
+    if let Some(image) = &arena.background.image {
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    custom_size: Some(arena.size),
+                    ..default()
+                },
+                transform: Transform::from_translation(arena.center().extend(BACKGROUND_Z)),
+                texture: asset_server.load(image.as_str()),
+                ..default()
             },
+            ArenaFloor {},
         ));
     }
 }
 
+//function for spawning a single wall entity
+pub fn spawn_wall(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    position: Vec2,
+    shape: WallShape,
+) -> Entity {
+    let transform = Transform::from_translation(position.extend(0.0));
+    let sprite_bundle = match shape {
+        WallShape::Rect { half_size } => SpriteBundle {
+            sprite: Sprite {
+                color: WALL_COLOR,
+                custom_size: Some(half_size * 2.0),
+                ..default()
+            },
+            transform,
+            ..default()
+        },
+        WallShape::Circle { radius } => SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(radius * 2.0)),
+                ..default()
+            },
+            transform,
+            texture: asset_server.load("sprites/ball_grey_large.png"),
+            ..default()
+        },
+    };
+
+    commands.spawn((sprite_bundle, Wall { shape })).id()
+}
+
+/*
+ function for finding a random position in the spawn regions, inside the boundary and not overlapping any wall, with parameter
+ - arena to pick a point from
+ - regions to spawn in, empty means the whole arena
+ - wall_query to get every wall
+ - radius of the thing being placed
+*/
+pub fn random_open_position(
+    arena: &Arena,
+    regions: &[SpawnRegion],
+    wall_query: &Query<(&Transform, &Wall)>,
+    radius: f32,
+) -> Vec3 {
+    let mut position = Vec2::ZERO;
+    for _ in 0..OPEN_POSITION_ATTEMPTS {
+        position = arena.random_point(regions);
+        if is_open_position(arena, wall_query, position, radius) {
+            break;
+        }
+    }
+    position.extend(0.0)
+}
+
+//true when a circle at position is inside the boundary and clear of every wall
+pub fn is_open_position(
+    arena: &Arena,
+    wall_query: &Query<(&Transform, &Wall)>,
+    position: Vec2,
+    radius: f32,
+) -> bool {
+    arena.is_inside(position, radius)
+        && !wall_query.iter().any(|(transform, wall)| {
+            wall.shape
+                .collide_circle(transform.translation.truncate(), position, radius)
+                .is_some()
+        })
+}
+
+//system for using the control scheme picked in the settings
+pub fn apply_control_scheme(
+    settings: Res<Settings>,
+    mut movement_settings: ResMut<MovementSettings>,
+) {
+    if settings.is_changed() && movement_settings.model != settings.control_scheme {
+        movement_settings.model = settings.control_scheme;
+    }
+}
+
 /*
-system untuk spawn camera dengan parameter
- - commands untuk memberikan command,
- - window_query untuk mendapatkan width dan height pada window
+ system for smoothly following the player with the camera, with parameter
+ - player_query to get the player's position
+ - camera_query to move the camera, the projection tells how much of the world is on screen
+ - arena to keep the view inside the world bounds
+ - time to make the smoothing frame rate independent
 */
-pub fn spawn_camera(mut commands: Commands, window_query: Query<&Window, With<PrimaryWindow>>) {
-    let window = window_query.get_single().unwrap(); //mendapatkan referensi pada window
+pub fn camera_follow(
+    player_query: Query<&Transform, (With<Player>, Without<GameCamera>)>,
+    mut camera_query: Query<(&mut Transform, &OrthographicProjection), With<GameCamera>>,
+    arena: Res<Arena>,
+    time: Res<Time>,
+) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let Ok((mut camera_transform, projection)) = camera_query.get_single_mut() else {
+        return;
+    };
 
-    commands.spawn(Camera2dBundle {
-        transform: Transform::from_xyz(window.width() / 2.0, window.height() / 2.0, 0.0), //set position of camera to the middle of the screen
-        ..default()
-    });
+    let half_view = projection.area.size() / 2.0;
+    let camera = camera_transform.translation.truncate();
+    let target = player_transform.translation.truncate();
+
+    //close a fixed fraction of the gap every second, however long the frame was
+    let t = 1.0 - (-CAMERA_FOLLOW_RATE * time.delta_seconds()).exp();
+    let position = arena.clamp_view(camera.lerp(target, t), half_view);
+    camera_transform.translation = position.extend(camera_transform.translation.z);
 }
 
 /*
  system untuk star enemy dengan parameter
  - commands untuk memberikan command,
- - window_query untuk mendapatkan width dan height pada window,
+ - arena untuk mendapatkan width dan height pada arena,
  - asset_server untuk menggunakan asset
 */
 pub fn spawn_stars(
     mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    arena: Res<Arena>,
     asset_server: Res<AssetServer>,
+    star_spawn_table: Res<StarSpawnTable>,
+    mut entity_pool: ResMut<EntityPool>,
+    wall_query: Query<(&Transform, &Wall)>,
 ) {
-    let window = window_query.get_single().unwrap(); //mendapatkan referensi pada window
-
     for _ in 0..NUMBER_OF_STARS {
-        //get random position
-        let random_x = random::<f32>() * window.width();
-        let random_y = random::<f32>() * window.height();
+        //get random position outside the walls
+        let position = random_open_position(
+            &arena,
+            &arena.star_spawn_regions,
+            &wall_query,
+            STAR_SIZE / 2.0,
+        );
 
-        //spawning, must be inside bundle, since we write more than one component
-        commands.spawn((
-            SpriteBundle {
-                transform: Transform::from_xyz(random_x, random_y, 0.0),
-                texture: asset_server.load("sprites/star.png"),
-                ..default()
-            },
-            Star {},
+        spawn_star(
+            &mut commands,
+            &mut entity_pool,
+            &asset_server,
+            position,
+            star_spawn_table.choose(),
+        );
+    }
+}
+
+/*
+ function for spawning a single star, reusing a pooled entity when there is one, with parameter
+ - commands untuk memberikan command,
+ - entity_pool to take a hidden star from
+ - asset_server untuk menggunakan asset
+ - position where the star is placed
+ - kind of star to spawn
+*/
+pub fn spawn_star(
+    commands: &mut Commands,
+    entity_pool: &mut EntityPool,
+    asset_server: &AssetServer,
+    position: Vec3,
+    kind: StarKind,
+) {
+    let sprite = Sprite {
+        color: kind.color(),
+        ..default()
+    };
+
+    //reuse a hidden star if there is one
+    if let Some(entity) = entity_pool.stars.pop() {
+        commands.entity(entity).insert((
+            sprite,
+            Transform::from_translation(position),
+            Visibility::Inherited,
+            Star::new(kind),
         ));
+        return;
     }
+
+    //spawning, must be inside bundle, since we write more than one component
+    commands.spawn((
+        SpriteBundle {
+            sprite,
+            transform: Transform::from_translation(position),
+            texture: asset_server.load("sprites/star.png"),
+            ..default()
+        },
+        Star::new(kind),
+    ));
 }
 
 /*
 system untuk spawn player dengan parameter
 - keyboard input dengan tipe Resource Input Keycode untuk mengambil input,
  - player_query, untuk mendapat player dengan cara mendapatkan transform yang memiliki player(transform mut karena kita akan modifikasi value variable yang ditunjuk),
+ - movement_settings untuk memilih direct atau inertial movement
  - dan time untuk normalisasi waktu nanti
 */
 pub fn player_movement(
     keyboard_input: Res<Input<KeyCode>>,
-    mut player_query: Query<&mut Transform, With<Player>>,
+    mut player_query: Query<
+        (&mut Transform, &mut Velocity, &ActiveEffects, &mut Dash),
+        With<Player>,
+    >,
+    movement_settings: Res<MovementSettings>,
+    settings: Res<Settings>,
     time: Res<Time>,
 ) {
+    let key_bindings = &settings.key_bindings;
     //akan memberikan true jika reference player_query ada dan mutable, kemudian mendapatkan transform
-    if let Ok(mut transform) = player_query.get_single_mut() {
+    if let Ok((mut transform, mut velocity, active_effects, mut dash)) =
+        player_query.get_single_mut()
+    {
         let mut direction = Vec3::ZERO;
 
         //move towards direction depends on the input
-        if keyboard_input.pressed(KeyCode::Left) || keyboard_input.pressed(KeyCode::A) {
+        if key_bindings.pressed(&keyboard_input, KeyAction::Left) {
             direction += Vec3::new(-1.0, 0.0, 0.0);
         }
-        if keyboard_input.pressed(KeyCode::Right) || keyboard_input.pressed(KeyCode::D) {
+        if key_bindings.pressed(&keyboard_input, KeyAction::Right) {
             direction += Vec3::new(1.0, 0.0, 0.0);
         }
-        if keyboard_input.pressed(KeyCode::Up) || keyboard_input.pressed(KeyCode::W) {
+        if key_bindings.pressed(&keyboard_input, KeyAction::Up) {
             direction += Vec3::new(0.0, 1.0, 0.0);
         }
-        if keyboard_input.pressed(KeyCode::Down) || keyboard_input.pressed(KeyCode::S) {
+        if key_bindings.pressed(&keyboard_input, KeyAction::Down) {
             direction += Vec3::new(0.0, -1.0, 0.0);
         }
 
         //normalize
         if direction.length() > 0.0 {
             direction = direction.normalize();
+            dash.last_direction = direction.truncate();
+        }
+
+        //speed boost power-up
+        let boost = if active_effects.is_active(PowerUpKind::SpeedBoost) {
+            SPEED_BOOST_MULTIPLIER
+        } else {
+            1.0
+        };
+        let delta = time.delta_seconds();
+
+        //dash, keeps going in the same direction until it's over
+        dash.tick(delta);
+        if key_bindings.just_pressed(&keyboard_input, KeyAction::Dash) && dash.is_ready() {
+            dash.start();
+        }
+
+        if dash.is_dashing() {
+            velocity.value = dash.direction * PLAYER_SPEED * boost * DASH_SPEED_MULTIPLIER;
+        } else {
+            match movement_settings.model {
+                MovementModel::Direct => {
+                    velocity.value = direction.truncate() * PLAYER_SPEED * boost;
+                }
+                MovementModel::Inertial => {
+                    //accelerate toward the input, slow down with friction when there is none
+                    if direction.length() > 0.0 {
+                        velocity.value +=
+                            direction.truncate() * movement_settings.acceleration * delta;
+                    } else {
+                        velocity.value *= (1.0 - movement_settings.friction * delta).max(0.0);
+                    }
+                    velocity.value = velocity
+                        .value
+                        .clamp_length_max(movement_settings.max_speed * boost);
+                }
+            }
         }
 
         //move_player
-        transform.translation += direction * PLAYER_SPEED * time.delta_seconds();
+        transform.translation += velocity.value.extend(0.0) * delta;
+    }
+}
+
+/*
+ system for leaving afterimages behind the player while dashing, with parameter
+ - player_query to get the player's position, sprite and dash
+ - settings to leave them out with reduced motion
+*/
+pub fn spawn_dash_afterimages(
+    mut commands: Commands,
+    player_query: Query<(&Transform, &Handle<Image>, &Dash), With<Player>>,
+    settings: Res<Settings>,
+) {
+    if let Ok((transform, texture, dash)) = player_query.get_single() {
+        if !dash.is_dashing() || settings.reduced_motion {
+            return;
+        }
+
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: Color::rgba(0.5, 0.7, 1.0, 0.5),
+                    ..default()
+                },
+                //drawn just behind the player
+                transform: Transform::from_translation(transform.translation - Vec3::Z * 0.1),
+                texture: texture.clone(),
+                ..default()
+            },
+            Afterimage {
+                timer: Timer::from_seconds(AFTERIMAGE_TIME, TimerMode::Once),
+            },
+        ));
+    }
+}
+
+//system for fading out afterimages and removing them once they are invisible
+pub fn fade_afterimages(
+    mut commands: Commands,
+    mut afterimage_query: Query<(Entity, &mut Sprite, &mut Afterimage)>,
+    time: Res<Time>,
+) {
+    for (entity, mut sprite, mut afterimage) in afterimage_query.iter_mut() {
+        afterimage.timer.tick(time.delta());
+        if afterimage.timer.finished() {
+            commands.entity(entity).despawn();
+        } else {
+            sprite.color.set_a(0.5 * afterimage.timer.percent_left());
+        }
     }
 }
 
 /*
  system untuk membuat player tetap ada dalam layar dengan parameter
- - player_query, untuk mendapat player dengan cara mendapatkan transform yang memiliki player(transform mut karena kita akan modifikasi value variable yang ditunjuk),
- - window_query untuk mendapatkan width dan height pada window,
+ - player_query, untuk mendapat player dengan cara mendapatkan transform yang memiliki player(transform mut karena kita akan modifikasi value variable yang ditunjuk), velocity to bounce off the edges
+ - arena untuk mendapatkan width dan height pada arena, and its boundary
+ - movement_settings to know whether the player should bounce
 */
 pub fn confine_player_movement(
-    mut player_query: Query<&mut Transform, With<Player>>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut player_query: Query<(&mut Transform, &mut Velocity), With<Player>>,
+    arena: Res<Arena>,
+    movement_settings: Res<MovementSettings>,
 ) {
     //akan memberikan true jika reference player_query ada dan mutable, kemudian mendapatkan transform
-    if let Ok(mut player_transform) = player_query.get_single_mut() {
-        let window = window_query.get_single().unwrap(); //mendapatkan referensi pada window
+    if let Ok((mut player_transform, mut velocity)) = player_query.get_single_mut() {
+        //in a wrapping arena the player comes back in at the opposite edge instead
+        if arena.boundary == Boundary::Wrap {
+            player_transform.translation = arena.wrap(player_transform.translation);
+            return;
+        }
+
+        //in a circular arena the player is kept inside the rim, bouncing off it with inertial movement
+        if arena.boundary == Boundary::Circle {
+            let position = player_transform.translation.truncate();
+            if let Some(normal) = arena.rim_normal(position, PLAYER_SIZE / 2.0) {
+                let clamped = arena.clamp_to_rim(position, PLAYER_SIZE / 2.0);
+                player_transform.translation = clamped.extend(player_transform.translation.z);
+
+                let into_rim = velocity.value.dot(normal);
+                if movement_settings.model == MovementModel::Inertial && into_rim > 0.0 {
+                    velocity.value -= (1.0 + movement_settings.restitution) * into_rim * normal;
+                }
+            }
+            return;
+        }
 
         let half_player_size: f32 = PLAYER_SIZE / 2.0;
 
         let x_min: f32 = 0.0 + half_player_size;
-        let x_max: f32 = window.width() - half_player_size;
+        let x_max: f32 = arena.width() - half_player_size;
         let y_min: f32 = 0.0 + half_player_size;
-        let y_max: f32 = window.height() - half_player_size;
+        let y_max: f32 = arena.height() - half_player_size;
 
         let mut translation = player_transform.translation;
+        let mut hit_x = false; //whether the player hit a vertical edge while moving into it
+        let mut hit_y = false;
 
         //bound player's x position
         if translation.x < x_min {
             translation.x = x_min;
+            hit_x = velocity.value.x < 0.0;
         } else if translation.x > x_max {
             translation.x = x_max;
+            hit_x = velocity.value.x > 0.0;
         }
 
         //bound player's y position
         if translation.y < y_min {
             translation.y = y_min;
+            hit_y = velocity.value.y < 0.0;
         } else if translation.y > y_max {
             translation.y = y_max;
+            hit_y = velocity.value.y > 0.0;
         }
 
         player_transform.translation = translation;
+
+        //with inertial movement the ball bounces off the edge, losing some speed
+        if movement_settings.model == MovementModel::Inertial {
+            if hit_x {
+                velocity.value.x *= -movement_settings.restitution;
+            }
+            if hit_y {
+                velocity.value.y *= -movement_settings.restitution;
+            }
+        }
+    }
+}
+
+/*
+ system for pushing the player out of walls, with parameter
+ - player_query to move the player and stop (or bounce) its velocity
+ - wall_query to get every wall
+ - movement_settings for the bounce restitution
+*/
+pub fn player_hit_wall(
+    mut player_query: Query<(&mut Transform, &mut Velocity), With<Player>>,
+    wall_query: Query<(&Transform, &Wall), Without<Player>>,
+    movement_settings: Res<MovementSettings>,
+) {
+    if let Ok((mut player_transform, mut velocity)) = player_query.get_single_mut() {
+        for (wall_transform, wall) in wall_query.iter() {
+            let Some((normal, depth)) = wall.shape.collide_circle(
+                wall_transform.translation.truncate(),
+                player_transform.translation.truncate(),
+                PLAYER_SIZE / 2.0,
+            ) else {
+                continue;
+            };
+
+            player_transform.translation += (normal * depth).extend(0.0);
+
+            //remove (or bounce) the part of the velocity going into the wall
+            let into_wall = velocity.value.dot(normal);
+            if into_wall < 0.0 {
+                let bounce = match movement_settings.model {
+                    MovementModel::Direct => 1.0,
+                    MovementModel::Inertial => 1.0 + movement_settings.restitution,
+                };
+                velocity.value -= normal * into_wall * bounce;
+            }
+        }
+    }
+}
+
+/*
+ system for reflecting enemies off walls, with parameter
+ - enemy_query to move the enemies out of the walls and reflect their direction
+ - wall_query to get every wall
+*/
+pub fn enemy_hit_wall(
+    mut enemy_query: Query<(&mut Transform, &mut Enemy), Without<Wall>>,
+    wall_query: Query<(&Transform, &Wall)>,
+) {
+    for (mut enemy_transform, mut enemy) in enemy_query.iter_mut() {
+        for (wall_transform, wall) in wall_query.iter() {
+            let Some((normal, depth)) = wall.shape.collide_circle(
+                wall_transform.translation.truncate(),
+                enemy_transform.translation.truncate(),
+                enemy.kind.size() / 2.0,
+            ) else {
+                continue;
+            };
+
+            enemy_transform.translation += (normal * depth).extend(0.0);
+
+            //reflect about the surface normal if heading into the wall
+            let into_wall = enemy.direction.dot(normal);
+            if into_wall < 0.0 {
+                enemy.direction -= 2.0 * into_wall * normal;
+            }
+        }
     }
 }
 
 /*
  system untuk menggerakkan enemy, dengan parameter
- - enemy_query, untuk mendapat enemy dengan cara mendapatkan transform yang memiliki enemy(transform mut karena kita akan modifikasi value variable yang ditunjuk),
+ - enemy_query, untuk mendapat enemy dengan cara mendapatkan transform yang memiliki enemy(transform mut karena kita akan modifikasi value variable yang ditunjuk), enemy mut because steering changes its direction
+ - player_query, for chasers to find the player and to check whether slow-motion is active
+ - swarm_grid, for swarm members to find their neighbours
+ - arena, for directions that cross the seam of a wrapping arena
  - time, untuk gerakan independen
 */
-pub fn enemy_movement(mut enemy_query: Query<(&mut Transform, &Enemy)>, time: Res<Time>) {
+pub fn enemy_movement(
+    mut enemy_query: Query<(&mut Transform, &mut Enemy, Option<&Boss>), Without<Player>>,
+    player_query: Query<(&Transform, &ActiveEffects), With<Player>>,
+    swarm_grid: Res<SwarmGrid>,
+    arena: Res<Arena>,
+    time: Res<Time>,
+) {
+    let player = player_query.get_single().ok();
+
+    //slow-motion power-up slows every enemy down
+    let speed_factor = match player {
+        Some((_, active_effects)) if active_effects.is_active(PowerUpKind::SlowMotion) => {
+            SLOW_MOTION_FACTOR
+        }
+        _ => 1.0,
+    };
+    let delta = time.delta_seconds();
+
     //loop on every enemy and get transform and enemy component
-    for (mut transform, enemy) in enemy_query.iter_mut() {
+    for (mut transform, mut enemy, boss) in enemy_query.iter_mut() {
+        let position = transform.translation.truncate();
+        let mut speed = enemy.kind.speed();
+
+        //steer depending on the enemy's behaviour
+        match enemy.kind {
+            EnemyKind::Bouncer => {}
+            EnemyKind::Boss => {
+                //the boss drifts like a bouncer at first, then hunts the player
+                let phase = boss.map_or(BossPhase::Drifting, |boss| boss.phase);
+                speed *= phase.speed_factor();
+                if let (BossPhase::Hunting | BossPhase::Enraged, Some((player_transform, _))) =
+                    (phase, player)
+                {
+                    let desired = arena.offset(position, player_transform.translation.truncate());
+                    if desired.length() > 0.0 {
+                        let angle = enemy.direction.angle_between(desired);
+                        let max_turn = CHASER_TURN_RATE * delta;
+                        let turn = angle.clamp(-max_turn, max_turn);
+                        enemy.direction = Vec2::from_angle(turn).rotate(enemy.direction);
+                    }
+                }
+            }
+            EnemyKind::Chaser => {
+                if let Some((player_transform, _)) = player {
+                    let desired = arena.offset(position, player_transform.translation.truncate());
+                    if desired.length() > 0.0 {
+                        //turn toward the player, but no faster than the turn rate
+                        let angle = enemy.direction.angle_between(desired);
+                        let max_turn = CHASER_TURN_RATE * delta;
+                        let turn = angle.clamp(-max_turn, max_turn);
+                        enemy.direction = Vec2::from_angle(turn).rotate(enemy.direction);
+                    }
+                }
+            }
+            EnemyKind::Wanderer => {
+                let turn = (random::<f32>() * 2.0 - 1.0) * WANDER_TURN_RATE * delta;
+                enemy.direction = Vec2::from_angle(turn).rotate(enemy.direction);
+            }
+            EnemyKind::Orbiter => {
+                let offset = arena.offset(enemy.anchor, position);
+                let distance = offset.length();
+                if distance > 0.0 {
+                    //move along the circle, correcting toward the orbit radius
+                    let outward = offset / distance;
+                    let tangent = outward.perp();
+                    let correction = (ORBIT_RADIUS - distance) / ORBIT_RADIUS;
+                    enemy.direction = (tangent + outward * correction).normalize_or_zero();
+                }
+            }
+            EnemyKind::Swarm => {
+                //pull toward the nearest copy of the player when the arena wraps
+                let player_position = player.map(|(transform, _)| {
+                    position + arena.offset(position, transform.translation.truncate())
+                });
+                let steering = swarm_steering(&swarm_grid, position, player_position);
+                let direction = enemy.direction + steering * SWARM_STEERING_RATE * delta;
+                if direction.length() > 0.0 {
+                    enemy.direction = direction.normalize();
+                }
+            }
+        }
+
         let direction = Vec3::new(enemy.direction.x, enemy.direction.y, 0.0); //create vector 3 with random value for enemy's direction
-        transform.translation += direction * ENEMY_SPEED * time.delta_seconds();
+        transform.translation += direction * speed * speed_factor * delta;
         //move the enemy
     }
 }
 
+/*
+ function for the boids rules of a swarm member, returning the steering direction, with parameter
+ - swarm_grid to look up the neighbours
+ - position of the member
+ - player_position for the weak pull toward the player, if there is a player
+*/
+fn swarm_steering(swarm_grid: &SwarmGrid, position: Vec2, player_position: Option<Vec2>) -> Vec2 {
+    let mut separation = Vec2::ZERO;
+    let mut alignment = Vec2::ZERO;
+    let mut center = Vec2::ZERO;
+    let mut count = 0;
+
+    for (other_position, other_direction) in swarm_grid.neighbours(position) {
+        let offset = position - *other_position;
+        let distance = offset.length();
+        if distance == 0.0 {
+            continue; //this is the member itself
+        }
+
+        //separation, pushing away harder the closer the neighbour is
+        if distance < SWARM_SEPARATION_RADIUS {
+            separation += offset / (distance * distance) * SWARM_SEPARATION_RADIUS;
+        }
+        alignment += *other_direction;
+        center += *other_position;
+        count += 1;
+    }
+
+    let mut steering = separation * SWARM_SEPARATION_WEIGHT;
+    if count > 0 {
+        //alignment follows the neighbours' heading, cohesion moves toward their center
+        steering += alignment.normalize_or_zero() * SWARM_ALIGNMENT_WEIGHT;
+        steering += (center / count as f32 - position).normalize_or_zero() * SWARM_COHESION_WEIGHT;
+    }
+    if let Some(player_position) = player_position {
+        steering += (player_position - position).normalize_or_zero() * SWARM_PLAYER_PULL_WEIGHT;
+    }
+    steering
+}
+
+/*
+ system for rebuilding the swarm grid before the enemies move, with parameter
+ - enemy_query to get every swarm member
+ - swarm_grid to fill
+*/
+pub fn update_swarm_grid(
+    enemy_query: Query<(&Transform, &Enemy)>,
+    mut swarm_grid: ResMut<SwarmGrid>,
+) {
+    swarm_grid.clear();
+    for (transform, enemy) in enemy_query.iter() {
+        if enemy.kind == EnemyKind::Swarm {
+            swarm_grid.insert(transform.translation.truncate(), enemy.direction);
+        }
+    }
+}
+
 /* system untuk membuat enemy tetap ada dalam layar dengan parameter
  - enemy_query, untuk mendapat enemy dengan cara mendapatkan transform yang memiliki enemy,(enemy mut karena kita akan modifikasi value variable yang ditunjuk)
- - window_query untuk mendapatkan width dan height pada window,
- - audio, untuk efek suara
- - asset server untuk sprite
+ - arena untuk mendapatkan width dan height pada arena,
+ - enemy_bounced_event_writer to let the audio know about the bounce
 */
 
 pub fn update_enemy_direction(
     mut enemy_query: Query<(&Transform, &mut Enemy)>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
-    audio: Res<Audio>,
-    asset_server: Res<AssetServer>,
+    arena: Res<Arena>,
+    mut enemy_bounced_event_writer: EventWriter<EnemyBounced>,
 ) {
-    let window = window_query.get_single().unwrap(); //mendapatkan referensi pada window
-
-    let half_enemy_size = ENEMY_SIZE / 2.0; //mendapatkan ukuran setengah dari enemy
-
-    //batas posisi
-    let x_min = 0.0 + half_enemy_size;
-    let x_max = window.width() - half_enemy_size;
-    let y_min = 0.0 + half_enemy_size;
-    let y_max = window.height() - half_enemy_size;
+    //nothing to bounce off in a wrapping arena
+    if arena.boundary == Boundary::Wrap {
+        return;
+    }
 
     //loop untuk tiap enemy
     for (transform, mut enemy) in enemy_query.iter_mut() {
+        let half_enemy_size = enemy.kind.size() / 2.0; //mendapatkan ukuran setengah dari enemy
+
+        //batas posisi
+        let x_min = 0.0 + half_enemy_size;
+        let x_max = arena.width() - half_enemy_size;
+        let y_min = 0.0 + half_enemy_size;
+        let y_max = arena.height() - half_enemy_size;
+
         let mut direction_changed: bool = false; //untuk mendeteksi apakah arah berubah
 
         let translation = transform.translation; //current position
 
-        //jika posisi diluar batas, kalikan direction dengan -1 untuk memberi efek memantul
-        if translation.x < x_min || translation.x > x_max {
-            enemy.direction.x *= -1.0;
-            direction_changed = true;
-        }
-        if translation.y < y_min || translation.y > y_max {
-            enemy.direction.y *= -1.0;
-            direction_changed = true;
+        if arena.boundary == Boundary::Circle {
+            //reflect about the rim's normal, only while heading outward so it doesn't flip back and forth
+            if let Some(normal) = arena.rim_normal(translation.truncate(), half_enemy_size) {
+                let into_rim = enemy.direction.dot(normal);
+                if into_rim > 0.0 {
+                    enemy.direction -= 2.0 * into_rim * normal;
+                    direction_changed = true;
+                }
+            }
+        } else {
+            //jika posisi diluar batas, kalikan direction dengan -1 untuk memberi efek memantul
+            if translation.x < x_min || translation.x > x_max {
+                enemy.direction.x *= -1.0;
+                direction_changed = true;
+            }
+            if translation.y < y_min || translation.y > y_max {
+                enemy.direction.y *= -1.0;
+                direction_changed = true;
+            }
         }
 
         if direction_changed {
-            //mengambil beberapa audio asset
-            let sound_effect_1 = asset_server.load("audio/pluck_001.ogg");
-            let sound_effect_2 = asset_server.load("audio/pluck_002.ogg");
-
-            //memilih sound effect secara random
-            let sound_effect = if random::<f32>() > 0.5 {
-                sound_effect_1
-            } else {
-                sound_effect_2
-            };
-
-            //memainkan audio
-            audio.play(sound_effect);
+            enemy_bounced_event_writer.send(EnemyBounced {
+                position: translation,
+            });
         }
     }
 }
@@ -275,22 +948,30 @@ pub fn update_enemy_direction(
 /*
  system for fixing bug of enemy stuck in the corner, with param
  -enemy_query is  untuk mendapat enemy dengan cara mendapatkan transform yang memiliki enemy,(transform mut karena kita akan modifikasi value variable yang ditunjuk)
- - window_query to get screen size
+ - arena to get the arena size and its boundary
 */
-pub fn cofine_enemy_movement(
-    mut enemy_query: Query<&mut Transform, With<Enemy>>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
-) {
-    let window = window_query.get_single().unwrap(); //mendapatkan referensi pada window
+pub fn cofine_enemy_movement(mut enemy_query: Query<(&mut Transform, &Enemy)>, arena: Res<Arena>) {
+    for (mut transform, enemy) in enemy_query.iter_mut() {
+        //in a wrapping arena enemies come back in at the opposite edge instead
+        if arena.boundary == Boundary::Wrap {
+            transform.translation = arena.wrap(transform.translation);
+            continue;
+        }
+
+        let half_enemy_size: f32 = enemy.kind.size() / 2.0;
 
-    let half_enemy_size: f32 = ENEMY_SIZE / 2.0;
+        //in a circular arena enemies are kept inside the rim
+        if arena.boundary == Boundary::Circle {
+            let clamped = arena.clamp_to_rim(transform.translation.truncate(), half_enemy_size);
+            transform.translation = clamped.extend(transform.translation.z);
+            continue;
+        }
 
-    let x_min: f32 = 0.0 + half_enemy_size;
-    let x_max: f32 = window.width() - half_enemy_size;
-    let y_min: f32 = 0.0 + half_enemy_size;
-    let y_max: f32 = window.height() - half_enemy_size;
+        let x_min: f32 = 0.0 + half_enemy_size;
+        let x_max: f32 = arena.width() - half_enemy_size;
+        let y_min: f32 = 0.0 + half_enemy_size;
+        let y_max: f32 = arena.height() - half_enemy_size;
 
-    for mut transform in enemy_query.iter_mut() {
         let mut translation = transform.translation; //current position
 
         //bound enemy's x position
@@ -314,65 +995,152 @@ pub fn cofine_enemy_movement(
 /*
  system for detecting enemy collision with player, with parameter
  - commands untuk memberikan command,
- - player_query, untuk mendapat ENTITY player dengan cara mendapatkan transform yang memiliki player, kita access entity karena kita ingon modif entity
- - enemy_query, untuk mendapat enemy dengan cara mendapatkan transform yang memiliki enemy
+ - player_query, untuk mendapat ENTITY player dengan cara mendapatkan transform yang memiliki player, kita access entity karena kita ingon modif entity, mutable to mark the player as hit
+ - enemy_query, untuk mendapat enemy dengan cara mendapatkan transform yang memiliki enemy, mutable to keep track of grazing
  - asset_server untuk menggunakan asset
- - audio untuk menggunakan audio
+ - player_hit_event_writer and grazed_event_writer to let the audio know
+ - score to award graze bonus points
+ - arena to measure distances across the seam of a wrapping arena
+ - time to count down the graze cooldowns
 */
+#[allow(clippy::too_many_arguments)]
 pub fn enemy_hit_player(
     mut commands: Commands,
     mut game_over_event_writer: EventWriter<GameOver>,
-    mut player_query: Query<(Entity, &Transform), With<Player>>,
-    enemy_query: Query<&Transform, With<Enemy>>,
+    mut player_query: Query<(Entity, &mut Player, &Transform, &ActiveEffects, &Dash)>,
+    mut enemy_query: Query<(&Transform, &mut Enemy)>,
     asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
-    score: Res<Score>,
+    mut player_hit_event_writer: EventWriter<PlayerHit>,
+    mut grazed_event_writer: EventWriter<Grazed>,
+    mut score: ResMut<Score>,
+    arena: Res<Arena>,
+    time: Res<Time>,
 ) {
     // jika terdaat player entity dan transform
-    if let Ok((player_entity, player_transform)) = player_query.get_single_mut() {
-        for enemy_transform in enemy_query.iter() {
-            let distance = player_transform
-                .translation
-                .distance(enemy_transform.translation); // get the distance between player and enemy
+    if let Ok((player_entity, mut player, player_transform, active_effects, dash)) =
+        player_query.get_single_mut()
+    {
+        //shield power-up and dash i-frames make the player immune to enemies
+        let shielded = active_effects.is_active(PowerUpKind::Shield) || dash.is_invulnerable();
+
+        for (enemy_transform, mut enemy) in enemy_query.iter_mut() {
+            let distance =
+                arena.distance(player_transform.translation, enemy_transform.translation); // get the distance between player and enemy
 
             let player_radius = PLAYER_SIZE / 2.0;
-            let enemy_radius = ENEMY_SIZE / 2.0;
+            let enemy_radius = enemy.kind.size() / 2.0;
+
+            enemy.graze_cooldown = (enemy.graze_cooldown - time.delta_seconds()).max(0.0);
 
             //jika terlalu dekat/bersentuhan
             if distance < player_radius + enemy_radius {
+                enemy.grazing = false; //touching doesn't count as a graze
+                if shielded || player.hit {
+                    continue;
+                }
+                player.hit = true;
+
                 println!("Collide with enemy, game over");
-                let sound_effect = asset_server.load("audio/explosionCrunch_000.ogg"); // get audio asset
-                audio.play(sound_effect); //play the audio
-                commands.entity(player_entity).despawn(); //despawning player
+                player_hit_event_writer.send(PlayerHit {
+                    position: player_transform.translation,
+                });
+                commands.entity(player_entity).despawn_recursive(); //despawning player
                 game_over_event_writer.send(GameOver { score: score.value });
+            } else if distance < player_radius + enemy_radius + GRAZE_DISTANCE {
+                //inside the graze ring, only start tracking when the cooldown is over
+                if enemy.graze_cooldown <= 0.0 {
+                    enemy.grazing = true;
+                }
+            } else if enemy.grazing {
+                //the enemy passed through the graze ring without touching the player
+                enemy.grazing = false;
+                enemy.graze_cooldown = GRAZE_COOLDOWN;
+                score.add_bonus_points(GRAZE_POINTS);
+
+                //"+N" popup between the player and the enemy
+                let offset = arena.offset(
+                    player_transform.translation.truncate(),
+                    enemy_transform.translation.truncate(),
+                );
+                let position = player_transform.translation + (offset / 2.0).extend(0.0);
+                grazed_event_writer.send(Grazed { position });
+                commands.spawn((
+                    Text2dBundle {
+                        text: Text::from_section(
+                            format!("+{}", GRAZE_POINTS),
+                            TextStyle {
+                                font: asset_server.load(HUD_FONT),
+                                font_size: HUD_FONT_SIZE,
+                                color: Color::CYAN,
+                            },
+                        ),
+                        transform: Transform::from_translation(position + Vec3::Z),
+                        ..default()
+                    },
+                    FloatingText {
+                        timer: Timer::from_seconds(FLOATING_TEXT_TIME, TimerMode::Once),
+                    },
+                ));
             }
         }
     }
 }
 
+/*
+ system for floating popup texts, moving them up and fading them out, with parameter
+ - floating_text_query to update every popup
+ - time to tick their timers
+ - settings to keep them still with reduced motion
+*/
+pub fn update_floating_text(
+    mut commands: Commands,
+    mut floating_text_query: Query<(Entity, &mut Transform, &mut Text, &mut FloatingText)>,
+    time: Res<Time>,
+    settings: Res<Settings>,
+) {
+    for (entity, mut transform, mut text, mut floating_text) in floating_text_query.iter_mut() {
+        floating_text.timer.tick(time.delta());
+        if floating_text.timer.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        if !settings.reduced_motion {
+            transform.translation.y += FLOATING_TEXT_SPEED * time.delta_seconds();
+        }
+        let alpha = floating_text.timer.percent_left();
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(alpha);
+        }
+    }
+}
+
 /*
  system for detecting star collision with player, with parameter
  - commands untuk memberikan command,
  - player_query, untuk mendapat player dengan cara mendapatkan transform yang memiliki player,
  - star_query, untuk mendapat ENITY enemy dengan cara mendapatkan transform yang memiliki star,kita access entity karena kita ingon modif entity
- - asset_server untuk menggunakan asset
- - audio untuk menggunakan audio
  - score untuk mengubah score
+ - combo to raise the combo multiplier
+ - star_collected_event_writer to let other systems, like the audio, know about the pickup
+ - arena to measure distances across the seam of a wrapping arena
 */
 pub fn player_hit_star(
     mut commands: Commands,
-    player_query: Query<&Transform, With<Player>>,
-    star_query: Query<(Entity, &Transform), With<Star>>,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
+    player_query: Query<(&Transform, &ActiveEffects), With<Player>>,
+    star_query: Query<(Entity, &Transform, &Star)>,
     mut score: ResMut<Score>,
+    mut combo: ResMut<Combo>,
+    mut star_collected_event_writer: EventWriter<StarCollected>,
+    arena: Res<Arena>,
 ) {
     // jika terdaat player entity dan transform
-    if let Ok(player_transform) = player_query.get_single() {
-        for (star_entity, star_transform) in star_query.iter() {
-            let distance = player_transform
-                .translation
-                .distance(star_transform.translation); // get the distance between player and star
+    if let Ok((player_transform, active_effects)) = player_query.get_single() {
+        //every score multiplier stack multiplies the star's value once more
+        let multiplier = 1 + active_effects.stacks(PowerUpKind::ScoreMultiplier);
+
+        for (star_entity, star_transform, star) in star_query.iter() {
+            let distance = arena.distance(player_transform.translation, star_transform.translation); // get the distance between player and star
 
             let player_radius = PLAYER_SIZE / 2.0;
             let star_radius = STAR_SIZE / 2.0;
@@ -380,14 +1148,146 @@ pub fn player_hit_star(
             //jika terlalu dekat/bersentuhan
             if distance < player_radius + star_radius {
                 println!("Collide with star");
-                score.value += 1; //menambah value score
-                let sound_effect = asset_server.load("audio/laserLarge_000.ogg"); // get audio asset
-                audio.play(sound_effect); //play the audio
-                commands.entity(star_entity).despawn(); //despawning star
+                combo.hit();
+
+                //menambah value score, keeping base points, combo bonus and power-up bonus apart
+                let base_points = star.value;
+                let combo_bonus = (base_points as f32 * (combo.multiplier() - 1.0)).round() as u32;
+                let bonus_points = (base_points + combo_bonus) * (multiplier - 1);
+                score.add_base_points(base_points);
+                score.add_combo_bonus(combo_bonus);
+                score.add_bonus_points(bonus_points);
+                EntityPool::release_star(&mut commands, star_entity); //hide the star and keep it for reuse
+                star_collected_event_writer.send(StarCollected {
+                    position: star_transform.translation,
+                    value: star.value,
+                });
             }
         }
     }
 }
+/*
+ system for moving drifting stars, bouncing them off walls and the arena edges, with parameter
+ - star_query to move every star that has a velocity
+ - wall_query to get every wall
+ - arena to get the arena size
+*/
+pub fn star_drift(
+    mut star_query: Query<(&mut Transform, &mut Star), Without<Wall>>,
+    wall_query: Query<(&Transform, &Wall)>,
+    arena: Res<Arena>,
+    time: Res<Time>,
+) {
+    let half_star_size = STAR_SIZE / 2.0;
+    let x_max = arena.width() - half_star_size;
+    let y_max = arena.height() - half_star_size;
+
+    for (mut transform, mut star) in star_query.iter_mut() {
+        if star.velocity == Vec2::ZERO {
+            continue;
+        }
+
+        transform.translation += star.velocity.extend(0.0) * time.delta_seconds();
+
+        //reflect off walls like the enemies do
+        for (wall_transform, wall) in wall_query.iter() {
+            let Some((normal, depth)) = wall.shape.collide_circle(
+                wall_transform.translation.truncate(),
+                transform.translation.truncate(),
+                half_star_size,
+            ) else {
+                continue;
+            };
+
+            transform.translation += (normal * depth).extend(0.0);
+            let into_wall = star.velocity.dot(normal);
+            if into_wall < 0.0 {
+                star.velocity -= 2.0 * into_wall * normal;
+            }
+        }
+
+        //in a wrapping arena stars drift through the edges instead
+        if arena.boundary == Boundary::Wrap {
+            transform.translation = arena.wrap(transform.translation);
+            continue;
+        }
+
+        //bounce off the rim of a circular arena
+        if arena.boundary == Boundary::Circle {
+            if let Some(normal) = arena.rim_normal(transform.translation.truncate(), half_star_size)
+            {
+                let into_rim = star.velocity.dot(normal);
+                if into_rim > 0.0 {
+                    star.velocity -= 2.0 * into_rim * normal;
+                }
+            }
+            continue;
+        }
+
+        //bounce off the edges
+        let translation = transform.translation;
+        if (translation.x < half_star_size && star.velocity.x < 0.0)
+            || (translation.x > x_max && star.velocity.x > 0.0)
+        {
+            star.velocity.x *= -1.0;
+        }
+        if (translation.y < half_star_size && star.velocity.y < 0.0)
+            || (translation.y > y_max && star.velocity.y > 0.0)
+        {
+            star.velocity.y *= -1.0;
+        }
+    }
+}
+
+/*
+ system for stars with a limited lifetime, with parameter
+ - star_query to tick each star's lifetime and flash its sprite
+ - time to count down the lifetime
+ - settings to fade instead of flashing with reduced motion
+*/
+pub fn tick_star_lifetime(
+    mut commands: Commands,
+    mut star_query: Query<(Entity, &mut Star, &mut Sprite)>,
+    time: Res<Time>,
+    settings: Res<Settings>,
+) {
+    for (star_entity, mut star, mut sprite) in star_query.iter_mut() {
+        let Some(lifetime) = star.lifetime.as_mut() else {
+            continue;
+        };
+        lifetime.tick(time.delta());
+
+        if lifetime.finished() {
+            EntityPool::release_star(&mut commands, star_entity);
+        } else if lifetime.remaining_secs() < STAR_FLASH_TIME && settings.reduced_motion {
+            //fade out steadily instead
+            sprite
+                .color
+                .set_a(lifetime.remaining_secs() / STAR_FLASH_TIME);
+        } else if lifetime.remaining_secs() < STAR_FLASH_TIME {
+            //flash by switching between opaque and faint
+            let flash_on = (lifetime.remaining_secs() * STAR_FLASH_RATE).fract() < 0.5;
+            sprite.color.set_a(if flash_on { 1.0 } else { 0.2 });
+        }
+    }
+}
+
+/*
+ system for decaying the combo, with parameter
+ - combo, losing one step each time the combo window lapses without a pickup
+ - time to tick the combo window
+*/
+pub fn tick_combo(mut combo: ResMut<Combo>, time: Res<Time>) {
+    if combo.count == 0 {
+        return;
+    }
+
+    combo.timer.tick(time.delta());
+    if combo.timer.just_finished() {
+        combo.count -= 1;
+    }
+}
+
 /*
 system untuk memeberi tahu ketika ada update score
 - score untuk membaca apakah ada perubahan pada value dari score
@@ -410,32 +1310,44 @@ pub fn tick_star_spawn_timer(mut star_spawn_timer: ResMut<StarSpawnTimer>, time:
 syatem untuk melakukan spawn tiapwaktu timer habis kemudian timer mengulang(durasi 1 detik)
  - commands untuk memberikan command,
  - asset_server untuk menggunakan asset
- - window_query untuk mendapatkan width dan height pada window,
+ - arena untuk mendapatkan width dan height pada arena,
  - star_spawn_timer mengetahui apakah sudah saatnya spawn
+ - star_spawn_table to pick the star kind
+ - star_query to count the live stars
+ - entity_limits for the maximum number of stars
+ - entity_pool to reuse collected stars
+ - wall_query so stars aren't placed inside walls
 */
+#[allow(clippy::too_many_arguments)]
 pub fn spawn_star_overtime(
     mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    arena: Res<Arena>,
     asset_server: Res<AssetServer>,
     star_spawn_timer: ResMut<StarSpawnTimer>,
+    star_spawn_table: Res<StarSpawnTable>,
+    star_query: Query<(), With<Star>>,
+    entity_limits: Res<EntityLimits>,
+    mut entity_pool: ResMut<EntityPool>,
+    wall_query: Query<(&Transform, &Wall)>,
 ) {
-    //jika waktu habis
-    if star_spawn_timer.timer.finished() {
-        let window = window_query.get_single().unwrap(); //mendapatkan referensi pada window
-
-        //get random position
-        let random_x = random::<f32>() * window.width();
-        let random_y = random::<f32>() * window.height();
+    //jika waktu habis, as long as the star cap isn't reached
+    if star_spawn_timer.timer.finished() && star_query.iter().count() < entity_limits.max_stars {
+        //get random position outside the walls
+        let position = random_open_position(
+            &arena,
+            &arena.star_spawn_regions,
+            &wall_query,
+            STAR_SIZE / 2.0,
+        );
 
-        //spawn bitang
-        commands.spawn((
-            SpriteBundle {
-                transform: Transform::from_xyz(random_x, random_y, 0.0),
-                texture: asset_server.load("sprites/star.png"),
-                ..default()
-            },
-            Star {},
-        ));
+        //spawn bitang, pick the star kind from the weighted spawn table
+        spawn_star(
+            &mut commands,
+            &mut entity_pool,
+            &asset_server,
+            position,
+            star_spawn_table.choose(),
+        );
     }
 }
 
@@ -443,46 +1355,669 @@ pub fn tick_enemy_spawn_timer(mut enemy_spawn_timer: ResMut<EnemySpawnTimer>, ti
     enemy_spawn_timer.timer.tick(time.delta());
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn spawn_enemies_overtime(
     mut commands: Commands,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    arena: Res<Arena>,
     asset_server: Res<AssetServer>,
     enemy_spawn_timer: Res<EnemySpawnTimer>,
+    enemy_query: Query<&Enemy>,
+    entity_limits: Res<EntityLimits>,
+    mut entity_pool: ResMut<EntityPool>,
+    enemy_spawn_table: Res<EnemySpawnTable>,
+    wall_query: Query<(&Transform, &Wall)>,
 ) {
-    //jika waktu habis
-    if enemy_spawn_timer.timer.finished() {
-        let window = window_query.get_single().unwrap(); //mendapatkan referensi pada window
+    //jika waktu habis, as long as the enemy cap isn't reached
+    let enemy_count = enemy_query
+        .iter()
+        .filter(|enemy| enemy.kind != EnemyKind::Swarm)
+        .count();
+    if enemy_spawn_timer.timer.finished() && enemy_count < entity_limits.max_enemies {
+        //get random position outside the walls
+        let position = random_open_position(
+            &arena,
+            &arena.enemy_spawn_regions,
+            &wall_query,
+            ENEMY_SIZE / 2.0,
+        );
+
+        spawn_enemy(
+            &mut commands,
+            &mut entity_pool,
+            &asset_server,
+            position,
+            enemy_spawn_table.choose(),
+        );
+    }
+}
 
-        //get random position
-        let random_x = random::<f32>() * window.width();
-        let random_y = random::<f32>() * window.height();
+pub fn tick_power_up_spawn_timer(
+    mut power_up_spawn_timer: ResMut<PowerUpSpawnTimer>,
+    time: Res<Time>,
+) {
+    power_up_spawn_timer.timer.tick(time.delta());
+}
+
+/*
+ system for spawning a power-up each time the timer finishes, with parameter
+ - arena to get the arena size
+ - power_up_spawn_table to pick which kind of power-up is spawned
+ - wall_query so power-ups aren't placed inside walls
+ - power_up_query and entity_limits so uncollected power-ups don't pile up
+*/
+pub fn spawn_power_up_overtime(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    power_up_spawn_timer: Res<PowerUpSpawnTimer>,
+    power_up_spawn_table: Res<PowerUpSpawnTable>,
+    wall_query: Query<(&Transform, &Wall)>,
+    power_up_query: Query<(), With<PowerUp>>,
+    entity_limits: Res<EntityLimits>,
+) {
+    if power_up_spawn_timer.timer.finished()
+        && power_up_query.iter().count() < entity_limits.max_power_ups
+    {
+        let Some(kind) = power_up_spawn_table.choose() else {
+            return;
+        };
+        //get random position outside the walls
+        let position = random_open_position(
+            &arena,
+            &arena.star_spawn_regions,
+            &wall_query,
+            POWER_UP_SIZE / 2.0,
+        );
 
-        //spawn bitang
         commands.spawn((
             SpriteBundle {
-                transform: Transform::from_xyz(random_x, random_y, 0.0),
-                texture: asset_server.load("sprites/ball_red_large.png"),
+                sprite: Sprite {
+                    color: kind.color(),
+                    custom_size: Some(Vec2::splat(POWER_UP_SIZE)),
+                    ..default()
+                },
+                transform: Transform::from_translation(position),
                 ..default()
             },
-            Enemy {
-                direction: Vec2::new(random::<f32>(), random::<f32>()).normalize(),
+            PowerUp { kind },
+        ));
+    }
+}
+
+/*
+ system for detecting power-up collision with player, with parameter
+ - player_query to get the player's position and its active effects
+ - power_up_query to get every power-up entity
+ - power_up_collected_event_writer to let the audio know about the pickup
+ - arena to measure distances across the seam of a wrapping arena
+*/
+pub fn player_hit_power_up(
+    mut commands: Commands,
+    mut player_query: Query<(&Transform, &mut ActiveEffects), With<Player>>,
+    power_up_query: Query<(Entity, &Transform, &PowerUp)>,
+    mut power_up_collected_event_writer: EventWriter<PowerUpCollected>,
+    arena: Res<Arena>,
+) {
+    if let Ok((player_transform, mut active_effects)) = player_query.get_single_mut() {
+        for (power_up_entity, power_up_transform, power_up) in power_up_query.iter() {
+            let distance =
+                arena.distance(player_transform.translation, power_up_transform.translation);
+
+            if distance < PLAYER_SIZE / 2.0 + POWER_UP_SIZE / 2.0 {
+                println!("Picked up {:?}", power_up.kind);
+                active_effects.apply(power_up.kind);
+                power_up_collected_event_writer.send(PowerUpCollected {
+                    kind: power_up.kind,
+                });
+                commands.entity(power_up_entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+//system to count down active effects and remove the ones that expired
+pub fn tick_active_effects(mut player_query: Query<&mut ActiveEffects>, time: Res<Time>) {
+    for mut active_effects in player_query.iter_mut() {
+        active_effects.tick(time.delta());
+    }
+}
+
+/*
+ system for the star magnet power-up, pulling nearby stars toward the player, with parameter
+ - player_query to get the player's position and its active effects
+ - star_query to move the stars
+ - arena to pull across the seam of a wrapping arena
+*/
+pub fn star_magnet(
+    player_query: Query<(&Transform, &ActiveEffects), With<Player>>,
+    mut star_query: Query<&mut Transform, (With<Star>, Without<Player>)>,
+    arena: Res<Arena>,
+    time: Res<Time>,
+) {
+    if let Ok((player_transform, active_effects)) = player_query.get_single() {
+        if !active_effects.is_active(PowerUpKind::StarMagnet) {
+            return;
+        }
+
+        for mut star_transform in star_query.iter_mut() {
+            let offset = arena.offset(
+                star_transform.translation.truncate(),
+                player_transform.translation.truncate(),
+            );
+            let distance = offset.length();
+
+            if distance > 0.0 && distance < STAR_MAGNET_RADIUS {
+                let step = (STAR_MAGNET_SPEED * time.delta_seconds()).min(distance);
+                star_transform.translation += (offset / distance * step).extend(0.0);
+            }
+        }
+    }
+}
+
+pub fn tick_swarm_spawn_timer(mut swarm_spawn_timer: ResMut<SwarmSpawnTimer>, time: Res<Time>) {
+    swarm_spawn_timer.timer.tick(time.delta());
+}
+
+/*
+ system for spawning a swarm wave each time the swarm timer finishes, with parameter
+ - arena to get the arena size
+ - enemy_query and entity_limits so the wave doesn't go over the swarm cap
+ - entity_pool to reuse hidden enemies
+ - wall_query so neither the wave nor its members start inside a wall
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_swarm_overtime(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    asset_server: Res<AssetServer>,
+    swarm_spawn_timer: Res<SwarmSpawnTimer>,
+    enemy_query: Query<&Enemy>,
+    entity_limits: Res<EntityLimits>,
+    mut entity_pool: ResMut<EntityPool>,
+    wall_query: Query<(&Transform, &Wall)>,
+) {
+    if !swarm_spawn_timer.timer.finished() {
+        return;
+    }
+
+    //swarm members have their own cap, so a wave doesn't stop the other enemies from spawning
+    let swarm_count = enemy_query
+        .iter()
+        .filter(|enemy| enemy.kind == EnemyKind::Swarm)
+        .count();
+    let room = entity_limits.max_swarm_enemies.saturating_sub(swarm_count);
+
+    //the whole wave starts bunched up around one random point outside the walls
+    let center = random_open_position(
+        &arena,
+        &arena.enemy_spawn_regions,
+        &wall_query,
+        SWARM_NEIGHBOUR_RADIUS,
+    )
+    .truncate();
+    for _ in 0..SWARM_WAVE_SIZE.min(room) {
+        //members are scattered around the center, falling back to the center if every spot is blocked
+        let radius = EnemyKind::Swarm.size() / 2.0;
+        let position = (0..OPEN_POSITION_ATTEMPTS)
+            .map(|_| {
+                let offset = Vec2::new(random::<f32>() - 0.5, random::<f32>() - 0.5)
+                    * SWARM_NEIGHBOUR_RADIUS;
+                center + offset
+            })
+            .find(|&position| is_open_position(&arena, &wall_query, position, radius))
+            .unwrap_or(center);
+        spawn_enemy(
+            &mut commands,
+            &mut entity_pool,
+            &asset_server,
+            position.extend(0.0),
+            EnemyKind::Swarm,
+        );
+    }
+}
+
+pub fn tick_boss_spawn_timer(mut boss_spawn_timer: ResMut<BossSpawnTimer>, time: Res<Time>) {
+    boss_spawn_timer.timer.tick(time.delta());
+}
+
+/*
+ system for spawning a boss each time the boss timer finishes, unless one is still alive, with parameter
+ - arena to get the arena size
+ - boss_query to check whether a boss is alive
+*/
+pub fn spawn_boss_overtime(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    asset_server: Res<AssetServer>,
+    boss_spawn_timer: Res<BossSpawnTimer>,
+    boss_query: Query<(), With<Boss>>,
+) {
+    if !boss_spawn_timer.timer.finished() || !boss_query.is_empty() {
+        return;
+    }
+
+    println!("A boss appears!");
+
+    //the boss enters from the top of the screen, never from the pool since it carries extra components
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(BOSS_SIZE)),
+                ..default()
             },
+            transform: Transform::from_xyz(arena.width() / 2.0, arena.height() - BOSS_SIZE, 0.0),
+            texture: asset_server.load(EnemyKind::Boss.texture()),
+            ..default()
+        },
+        Enemy::new(
+            EnemyKind::Boss,
+            Vec2::new(random::<f32>() - 0.5, -1.0).normalize(),
+        ),
+        Boss::default(),
+    ));
+}
+
+/*
+ system for damaging the boss with stars collected near it, with parameter
+ - star_collected_event_reader to know which stars were collected and where
+ - boss_query to update the boss's health and phase
+ - enemy_query and entity_limits so phase change minions don't go over the enemy caps
+ - entity_pool to spawn minions on phase changes
+ - boss_defeated_event_writer to announce the kill
+ - arena to measure distances across the seam of a wrapping arena
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn boss_hit_by_star(
+    mut commands: Commands,
+    mut star_collected_event_reader: EventReader<StarCollected>,
+    mut boss_query: Query<(Entity, &Transform, &mut Boss)>,
+    enemy_query: Query<&Enemy>,
+    asset_server: Res<AssetServer>,
+    mut score: ResMut<Score>,
+    entity_limits: Res<EntityLimits>,
+    mut entity_pool: ResMut<EntityPool>,
+    mut boss_defeated_event_writer: EventWriter<BossDefeated>,
+    arena: Res<Arena>,
+) {
+    for event in star_collected_event_reader.iter() {
+        for (boss_entity, boss_transform, mut boss) in boss_query.iter_mut() {
+            if boss.health <= 0.0
+                || arena.distance(event.position, boss_transform.translation) > BOSS_DAMAGE_RADIUS
+            {
+                continue;
+            }
+
+            boss.health -= event.value as f32 * BOSS_DAMAGE_PER_POINT;
+
+            if boss.health <= 0.0 {
+                score.add_bonus_points(BOSS_BONUS);
+                commands.entity(boss_entity).despawn_recursive();
+                boss_defeated_event_writer.send(BossDefeated {
+                    position: boss_transform.translation,
+                    bonus: BOSS_BONUS,
+                });
+                continue;
+            }
+
+            //a new phase calls in minions through the regular enemy spawn
+            let phase = BossPhase::from_health(boss.health / boss.max_health);
+            if phase != boss.phase {
+                boss.phase = phase;
+                println!("Boss phase: {:?}", phase);
+                let minion_kind = match phase {
+                    BossPhase::Enraged => EnemyKind::Swarm,
+                    _ => EnemyKind::Chaser,
+                };
+
+                //swarm minions count against the swarm cap, the others against the enemy cap
+                let (count, limit) = if minion_kind == EnemyKind::Swarm {
+                    let count = enemy_query
+                        .iter()
+                        .filter(|enemy| enemy.kind == EnemyKind::Swarm)
+                        .count();
+                    (count, entity_limits.max_swarm_enemies)
+                } else {
+                    let count = enemy_query
+                        .iter()
+                        .filter(|enemy| enemy.kind != EnemyKind::Swarm)
+                        .count();
+                    (count, entity_limits.max_enemies)
+                };
+                for _ in 0..BOSS_MINIONS_PER_PHASE.min(limit.saturating_sub(count)) {
+                    spawn_enemy(
+                        &mut commands,
+                        &mut entity_pool,
+                        &asset_server,
+                        boss_transform.translation,
+                        minion_kind,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/*
+ system for the enraged boss to keep calling minions, with parameter
+ - boss_query to tick the boss's minion timer
+ - enemy_query and entity_limits so minions don't go over the enemy cap
+ - entity_pool to reuse hidden enemies
+*/
+pub fn boss_spawn_minions(
+    mut commands: Commands,
+    mut boss_query: Query<(&Transform, &mut Boss)>,
+    asset_server: Res<AssetServer>,
+    enemy_query: Query<&Enemy>,
+    entity_limits: Res<EntityLimits>,
+    mut entity_pool: ResMut<EntityPool>,
+    time: Res<Time>,
+) {
+    for (boss_transform, mut boss) in boss_query.iter_mut() {
+        if boss.phase != BossPhase::Enraged {
+            continue;
+        }
+
+        boss.minion_timer.tick(time.delta());
+        let enemy_count = enemy_query
+            .iter()
+            .filter(|enemy| enemy.kind != EnemyKind::Swarm)
+            .count();
+        if boss.minion_timer.just_finished() && enemy_count < entity_limits.max_enemies {
+            spawn_enemy(
+                &mut commands,
+                &mut entity_pool,
+                &asset_server,
+                boss_transform.translation,
+                EnemyKind::Chaser,
+            );
+        }
+    }
+}
+
+pub fn handle_boss_defeated(mut boss_defeated_event_reader: EventReader<BossDefeated>) {
+    for event in boss_defeated_event_reader.iter() {
+        println!("Boss defeated! Bonus: {}", event.bonus);
+    }
+}
+
+/*
+ function for spawning a single projectile, reusing a pooled entity when there is one, with parameter
+ - commands untuk memberikan command,
+ - entity_pool to take a hidden projectile from
+ - asset_server untuk menggunakan asset
+ - position where the projectile starts
+ - velocity of the projectile
+*/
+pub fn spawn_projectile(
+    commands: &mut Commands,
+    entity_pool: &mut EntityPool,
+    asset_server: &AssetServer,
+    position: Vec3,
+    velocity: Vec2,
+) {
+    let projectile = Projectile { velocity };
+
+    //reuse a hidden projectile if there is one
+    if let Some(entity) = entity_pool.projectiles.pop() {
+        commands.entity(entity).insert((
+            Transform::from_translation(position),
+            Visibility::Inherited,
+            projectile,
         ));
+        return;
     }
+
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::splat(PROJECTILE_SIZE)),
+                ..default()
+            },
+            transform: Transform::from_translation(position),
+            texture: asset_server.load("sprites/ball_red_large.png"),
+            ..default()
+        },
+        projectile,
+    ));
 }
 
-pub fn exit_game(
-    keyboard_input: Res<Input<KeyCode>>,
-    mut app_exit_event_writer: EventWriter<AppExit>,
+/*
+ system for enemies firing their bullet patterns, with parameter
+ - enemy_query to tick each enemy's fire cooldown, the boss pattern follows its phase
+ - player_query so aimed patterns know where to shoot
+ - projectile_query and entity_limits so the projectiles don't go over the cap
+ - entity_pool to reuse hidden projectiles
+ - arena to aim across the seam of a wrapping arena
+*/
+#[allow(clippy::too_many_arguments)]
+pub fn enemy_fire(
+    mut commands: Commands,
+    mut enemy_query: Query<(&Transform, &mut Enemy, Option<&Boss>)>,
+    player_query: Query<&Transform, With<Player>>,
+    asset_server: Res<AssetServer>,
+    projectile_query: Query<(), With<Projectile>>,
+    entity_limits: Res<EntityLimits>,
+    mut entity_pool: ResMut<EntityPool>,
+    arena: Res<Arena>,
+    time: Res<Time>,
+) {
+    //nobody to shoot at
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+    let mut projectile_count = projectile_query.iter().count();
+
+    for (transform, mut enemy, boss) in enemy_query.iter_mut() {
+        let Some(fire_pattern) = boss
+            .map(|boss| boss.phase.fire_pattern())
+            .or_else(|| enemy.kind.fire_pattern())
+        else {
+            continue;
+        };
+
+        enemy.fire_cooldown -= time.delta_seconds();
+        if enemy.fire_cooldown > 0.0 {
+            continue;
+        }
+        enemy.fire_cooldown = fire_pattern.interval;
+
+        let aim = arena
+            .offset(
+                transform.translation.truncate(),
+                player_transform.translation.truncate(),
+            )
+            .normalize_or_zero();
+        for direction in fire_pattern.directions(aim, enemy.fire_angle) {
+            if projectile_count >= entity_limits.max_projectiles {
+                break;
+            }
+            spawn_projectile(
+                &mut commands,
+                &mut entity_pool,
+                &asset_server,
+                transform.translation,
+                direction * fire_pattern.speed,
+            );
+            projectile_count += 1;
+        }
+        if let BulletPattern::Spiral { step, .. } = fire_pattern.pattern {
+            enemy.fire_angle += step;
+        }
+    }
+}
+
+/*
+ system for moving projectiles and removing the ones that left the arena, with parameter
+ - projectile_query to move every projectile
+ - arena to get the arena size
+*/
+pub fn projectile_movement(
+    mut commands: Commands,
+    mut projectile_query: Query<(Entity, &mut Transform, &Projectile)>,
+    arena: Res<Arena>,
+    time: Res<Time>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Escape) {
-        app_exit_event_writer.send(AppExit);
+    //projectiles leave even a wrapping arena, otherwise they would never go away
+    let margin = PROJECTILE_SIZE / 2.0;
+
+    for (entity, mut transform, projectile) in projectile_query.iter_mut() {
+        transform.translation += projectile.velocity.extend(0.0) * time.delta_seconds();
+
+        //a negative radius lets the projectile get fully past the edge first
+        if !arena.is_inside(transform.translation.truncate(), -margin) {
+            EntityPool::release_projectile(&mut commands, entity);
+        }
     }
 }
 
-pub fn handle_game_over(mut game_over_event_reader: EventReader<GameOver>) {
+/*
+ system for detecting projectile collision with player, counted as an enemy hit, with parameter
+ - player_query to get the player, check for the shield power-up and dash i-frames and mark it as hit
+ - projectile_query to get every projectile
+ - player_hit_event_writer to let the audio know about the hit
+ - arena to measure distances across the seam of a wrapping arena
+*/
+pub fn projectile_hit_player(
+    mut commands: Commands,
+    mut game_over_event_writer: EventWriter<GameOver>,
+    mut player_query: Query<(Entity, &mut Player, &Transform, &ActiveEffects, &Dash)>,
+    projectile_query: Query<(Entity, &Transform), With<Projectile>>,
+    mut player_hit_event_writer: EventWriter<PlayerHit>,
+    score: Res<Score>,
+    arena: Res<Arena>,
+) {
+    if let Ok((player_entity, mut player, player_transform, active_effects, dash)) =
+        player_query.get_single_mut()
+    {
+        //the player was already hit by an enemy this frame
+        if player.hit {
+            return;
+        }
+
+        for (projectile_entity, projectile_transform) in projectile_query.iter() {
+            let distance = arena.distance(
+                player_transform.translation,
+                projectile_transform.translation,
+            );
+
+            if distance < PLAYER_SIZE / 2.0 + PROJECTILE_SIZE / 2.0 {
+                //dashing through projectiles leaves them alone
+                if dash.is_invulnerable() {
+                    continue;
+                }
+
+                EntityPool::release_projectile(&mut commands, projectile_entity);
+
+                //the shield absorbs projectiles
+                if active_effects.is_active(PowerUpKind::Shield) {
+                    continue;
+                }
+
+                println!("Hit by a projectile, game over");
+                player.hit = true;
+                player_hit_event_writer.send(PlayerHit {
+                    position: player_transform.translation,
+                });
+                commands.entity(player_entity).despawn_recursive();
+                game_over_event_writer.send(GameOver { score: score.value });
+                return;
+            }
+        }
+    }
+}
+
+//sprites that get wrap ghosts when the arena wraps
+type WrapGhostFilter = (
+    Or<(With<Player>, With<Enemy>, With<Star>, With<PowerUp>)>,
+    Without<WrapGhosts>,
+);
+
+/*
+ system for giving moving sprites their wrap ghosts when the arena wraps, with parameter
+ - arena to know whether it wraps
+ - sprite_query for the sprites that don't have ghosts yet
+*/
+pub fn spawn_wrap_ghosts(
+    mut commands: Commands,
+    arena: Res<Arena>,
+    sprite_query: Query<(Entity, &Sprite, &Handle<Image>), WrapGhostFilter>,
+) {
+    if arena.boundary != Boundary::Wrap {
+        return;
+    }
+
+    for (entity, sprite, texture) in sprite_query.iter() {
+        commands
+            .entity(entity)
+            .insert(WrapGhosts {})
+            .with_children(|parent| {
+                //one copy across each edge and one across the corner
+                for axes in [Vec2::X, Vec2::Y, Vec2::ONE] {
+                    parent.spawn((
+                        SpriteBundle {
+                            sprite: sprite.clone(),
+                            texture: texture.clone(),
+                            ..default()
+                        },
+                        WrapGhost { axes },
+                    ));
+                }
+            });
+    }
+}
+
+/*
+ system for keeping wrap ghosts on the far side of the arena and looking like their sprite, with parameter
+ - arena to get the arena size
+ - sprite_query for the sprites with ghosts
+ - ghost_query to move and restyle the ghosts
+*/
+pub fn update_wrap_ghosts(
+    arena: Res<Arena>,
+    sprite_query: Query<(&Transform, &Sprite, &Handle<Image>, &Children), With<WrapGhosts>>,
+    mut ghost_query: Query<
+        (&WrapGhost, &mut Transform, &mut Sprite, &mut Handle<Image>),
+        Without<WrapGhosts>,
+    >,
+) {
+    for (transform, sprite, texture, children) in sprite_query.iter() {
+        //ghosts sit one arena away toward the nearer edges, so they are only on screen while the sprite crosses the seam
+        let position = transform.translation.truncate();
+        let shift = Vec2::new(
+            if position.x < arena.width() / 2.0 {
+                arena.width()
+            } else {
+                -arena.width()
+            },
+            if position.y < arena.height() / 2.0 {
+                arena.height()
+            } else {
+                -arena.height()
+            },
+        );
+
+        for &child in children.iter() {
+            if let Ok((ghost, mut ghost_transform, mut ghost_sprite, mut ghost_texture)) =
+                ghost_query.get_mut(child)
+            {
+                ghost_transform.translation = (shift * ghost.axes).extend(0.0);
+                ghost_sprite.color = sprite.color;
+                ghost_sprite.custom_size = sprite.custom_size;
+                //pooled entities can come back as a different kind with a different texture
+                if *ghost_texture != *texture {
+                    *ghost_texture = texture.clone();
+                }
+            }
+        }
+    }
+}
+
+pub fn handle_game_over(mut game_over_event_reader: EventReader<GameOver>, score: Res<Score>) {
     for event in game_over_event_reader.iter() {
         println!("Your final score is {}", event.score.to_string());
+        println!(
+            "Stars: {} | Combo bonus: {} | Other bonuses: {}",
+            score.base_points, score.combo_bonus, score.bonus_points
+        );
     }
 }
 
@@ -500,3 +2035,64 @@ pub fn high_scores_updated(high_scores: Res<HighScores>) {
         println!("High Scores: {:?}", high_scores);
     }
 }
+
+//everything that belongs to a run and is thrown away when restarting
+type RunFilter = Or<(
+    With<Player>,
+    With<Enemy>,
+    With<Star>,
+    With<PowerUp>,
+    With<Projectile>,
+    With<Afterimage>,
+    With<FloatingText>,
+    With<BossHealthBar>,
+    With<GameOverScreen>,
+)>;
+
+//a run entity, with the components deciding whether it goes back to the pool
+type RunEntity<'a> = (
+    Entity,
+    Option<&'a Enemy>,
+    Option<&'a Boss>,
+    Option<&'a Star>,
+    Option<&'a Projectile>,
+);
+
+/*
+ system for throwing away the current run before a new one is spawned, with parameter
+ - run_query for every entity of the run, plain enemies, stars and projectiles go back to the pool
+ - camera_query and arena to put the camera back on the player start
+*/
+pub fn reset_run(
+    mut commands: Commands,
+    run_query: Query<RunEntity, RunFilter>,
+    mut camera_query: Query<(&mut Transform, &OrthographicProjection), With<GameCamera>>,
+    arena: Res<Arena>,
+) {
+    for (entity, enemy, boss, star, projectile) in run_query.iter() {
+        //the boss carries extra components, so it is never reused
+        if enemy.is_some() && boss.is_none() {
+            EntityPool::release_enemy(&mut commands, entity);
+        } else if star.is_some() {
+            EntityPool::release_star(&mut commands, entity);
+        } else if projectile.is_some() {
+            EntityPool::release_projectile(&mut commands, entity);
+        } else {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+
+    //score, combo and every spawn timer start over
+    commands.insert_resource(Score::default());
+    commands.insert_resource(Combo::default());
+    commands.insert_resource(StarSpawnTimer::default());
+    commands.insert_resource(EnemySpawnTimer::default());
+    commands.insert_resource(PowerUpSpawnTimer::default());
+    commands.insert_resource(SwarmSpawnTimer::default());
+    commands.insert_resource(BossSpawnTimer::default());
+
+    if let Ok((mut camera_transform, projection)) = camera_query.get_single_mut() {
+        let position = arena.clamp_view(arena.player_start, projection.area.size() / 2.0);
+        camera_transform.translation = position.extend(camera_transform.translation.z);
+    }
+}